@@ -1,6 +1,45 @@
 #![deny(missing_docs)]
 //! Protocol for trycp_server websocket messages.
 
+/// The version of the TryCP protocol described by this crate. It is increased whenever messages
+/// change in a way that clients and servers built against different versions can't understand
+/// each other.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Optional protocol features that clients can ask for in a [Request::Hello].
+///
+/// Clients that ask for `structured_errors` receive structured errors for all of their requests.
+/// Only clients that ask for `conductor_exit_notifications` and `admin_signals` receive
+/// `conductor_exited` and `admin_signal` notifications respectively.
+pub const FEATURES: &[&str] = &[
+    "structured_errors",
+    "conductor_exit_notifications",
+    "admin_signals",
+];
+
+/// The `type` of every [Request].
+pub const REQUEST_TYPES: &[&str] = &[
+    "hello",
+    "authenticate",
+    "save_dna",
+    "download_dna",
+    "configure_player",
+    "startup",
+    "shutdown",
+    "restart",
+    "pause",
+    "resume",
+    "remove_player",
+    "reset",
+    "call_admin_interface",
+    "connect_app_interface",
+    "disconnect_app_interface",
+    "call_app_interface",
+    "download_logs",
+    "list_players",
+    "player_status",
+];
+
 /// Requests must include a message id.
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -10,6 +49,17 @@ pub struct RequestWrapper {
 
     /// The request content.
     pub request: Request,
+
+    /// Whether errors should be returned as a [TryCpError] rather than as a plain message.
+    /// Clients which predate structured errors leave this unset.
+    #[serde(default)]
+    pub structured_errors: bool,
+
+    /// The namespace of the players the request refers to, so that several test runs can share a
+    /// server without their player ids clashing. [Request::Reset] and [Request::ListPlayers] only
+    /// apply to the players in the namespace.
+    #[serde(default)]
+    pub namespace: Option<String>,
 }
 
 /// Trycp server requests.
@@ -17,6 +67,24 @@ pub struct RequestWrapper {
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
 pub enum Request {
+    /// Exchange protocol versions and discover the server's capabilities. Clients should send
+    /// this before any other request.
+    Hello {
+        /// The [PROTOCOL_VERSION] the client was built against.
+        client_version: u32,
+
+        /// The [FEATURES] the client would like to use.
+        #[serde(default)]
+        features: Vec<String>,
+    },
+
+    /// Authenticate the connection, if the server requires it and the token wasn't sent as a
+    /// bearer token in the `Authorization` header of the websocket upgrade.
+    Authenticate {
+        /// One of the tokens the server accepts.
+        token: String,
+    },
+
     /// Given a DNA file, stores the DNA and returns the path at which it is stored.
     SaveDna {
         /// This is actually the dna filename.
@@ -49,6 +117,10 @@ pub enum Request {
         /// network: ~
         /// ```
         partial_config: String,
+
+        /// The name of the Holochain binary to run this player's conductor with, as registered
+        /// with the server. Uses the server's default binary if not set.
+        holochain_version: Option<String>,
     },
 
     /// Start a conductor.
@@ -58,6 +130,14 @@ pub enum Request {
 
         /// The log level of the conductor.
         log_level: Option<String>,
+
+        /// How long to wait for the conductor to become ready, in milliseconds. Defaults to 10
+        /// seconds.
+        timeout_ms: Option<u64>,
+
+        /// How to determine that the conductor is ready. Defaults to
+        /// [ReadinessProbe::LogLine].
+        readiness_probe: Option<ReadinessProbe>,
     },
 
     /// Shut down a conductor.
@@ -69,6 +149,48 @@ pub enum Request {
         signal: Option<String>,
     },
 
+    /// Shut down a conductor and start it up again, keeping its data.
+    Restart {
+        /// The id of the conductor to restart.
+        id: String,
+
+        /// The signal with which to shut down the conductor.
+        signal: Option<String>,
+
+        /// The log level of the restarted conductor.
+        log_level: Option<String>,
+
+        /// How long to wait for the restarted conductor to become ready, in milliseconds. Defaults
+        /// to 10 seconds.
+        timeout_ms: Option<u64>,
+
+        /// How to determine that the restarted conductor is ready. Defaults to
+        /// [ReadinessProbe::LogLine].
+        readiness_probe: Option<ReadinessProbe>,
+    },
+
+    /// Freeze a running conductor with SIGSTOP, keeping its state.
+    Pause {
+        /// The id of the conductor to pause.
+        id: String,
+    },
+
+    /// Thaw a paused conductor with SIGCONT.
+    Resume {
+        /// The id of the conductor to resume.
+        id: String,
+    },
+
+    /// Shut down a player's conductor and remove the player.
+    RemovePlayer {
+        /// The player id.
+        id: String,
+
+        /// Keep the player's directory with its configuration, data and logs, moved to the
+        /// `removed-players` directory of the server's data directory.
+        keep_data: bool,
+    },
+
     /// Shuts down all running conductors.
     Reset,
 
@@ -80,6 +202,10 @@ pub enum Request {
         /// The request.
         #[serde(with = "serde_bytes")]
         message: Vec<u8>,
+
+        /// How long to wait for the conductor's response, in milliseconds. Defaults to the
+        /// server's call timeout.
+        timeout_ms: Option<u64>,
     },
 
     /// Hook up an app interface.
@@ -105,6 +231,10 @@ pub enum Request {
         /// The request.
         #[serde(with = "serde_bytes")]
         message: Vec<u8>,
+
+        /// How long to wait for the conductor's response, in milliseconds. Defaults to the
+        /// server's call timeout.
+        timeout_ms: Option<u64>,
     },
 
     /// Request the logs for a player's conductor and keystore.
@@ -112,6 +242,27 @@ pub enum Request {
         /// The conductor id.
         id: String,
     },
+
+    /// Request the status of all configured players.
+    ListPlayers,
+
+    /// Request the status of a player.
+    PlayerStatus {
+        /// The player id.
+        id: String,
+    },
+}
+
+/// Strategies for determining that a conductor has started up.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessProbe {
+    /// Wait for the conductor to print its ready message on stdout.
+    #[default]
+    LogLine,
+
+    /// Poll the conductor's admin interface until it accepts a websocket connection.
+    AdminInterface,
 }
 
 /// Message response types.
@@ -124,6 +275,9 @@ pub enum MessageResponse {
 
     /// Encoded response.
     Bytes(Vec<u8>),
+
+    /// Text response, such as the path of a stored DNA.
+    Text(String),
 }
 
 impl MessageResponse {
@@ -132,6 +286,7 @@ impl MessageResponse {
         match self {
             Self::Null => Vec::new(),
             Self::Bytes(v) => v,
+            Self::Text(s) => s.into_bytes(),
         }
     }
 }
@@ -156,16 +311,209 @@ pub enum MessageToClient {
         id: u64,
 
         /// message content.
-        response: std::result::Result<MessageResponse, String>,
+        response: std::result::Result<MessageResponse, TryCpError>,
+    },
+
+    /// A conductor exited without having been shut down through the trycp server.
+    ConductorExited {
+        /// The id of the player whose conductor exited.
+        id: String,
+
+        /// How the conductor exited.
+        status: ConductorExitStatus,
+    },
+
+    /// A message emitted by a conductor on its admin interface.
+    AdminSignal {
+        /// The id of the player whose conductor emitted the signal.
+        id: String,
+
+        /// The content of the signal.
+        data: Vec<u8>,
+    },
+}
+
+/// An error returned by the trycp server in response to a request.
+///
+/// Servers which predate structured errors, or requests that did not ask for them, return a plain
+/// message, which is deserialized with [ErrorKind::Unknown].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(from = "TryCpErrorRepr")]
+pub struct TryCpError {
+    /// What kind of error occurred.
+    pub kind: ErrorKind,
+
+    /// A human readable description of the error.
+    pub message: String,
+
+    /// The id of the player the failed request was about, if any.
+    pub player_id: Option<String>,
+
+    /// The underlying cause of the error, if any.
+    pub details: Option<String>,
+}
+
+impl std::fmt::Display for TryCpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for TryCpError {}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum TryCpErrorRepr {
+    Message(String),
+    Structured {
+        kind: ErrorKind,
+        message: String,
+        player_id: Option<String>,
+        details: Option<String>,
     },
 }
 
+impl From<TryCpErrorRepr> for TryCpError {
+    fn from(repr: TryCpErrorRepr) -> Self {
+        match repr {
+            TryCpErrorRepr::Message(message) => Self {
+                kind: ErrorKind::Unknown,
+                message,
+                player_id: None,
+                details: None,
+            },
+            TryCpErrorRepr::Structured {
+                kind,
+                message,
+                player_id,
+                details,
+            } => Self {
+                kind,
+                message,
+                player_id,
+                details,
+            },
+        }
+    }
+}
+
+/// Kinds of errors returned by the trycp server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The client speaks a different protocol version than the server.
+    ProtocolMismatch,
+
+    /// The connection is not authenticated, or the given auth token is invalid.
+    Unauthenticated,
+
+    /// The request contained an invalid value, such as an unknown signal or a malformed URL.
+    InvalidRequest,
+
+    /// No player with the given id has been configured.
+    PlayerNotConfigured,
+
+    /// A player with the given id has already been configured.
+    PlayerAlreadyConfigured,
+
+    /// No holochain binary is registered under the requested version.
+    UnknownHolochainVersion,
+
+    /// All admin ports in the server's range have been assigned.
+    OutOfPorts,
+
+    /// The player's conductor is not running.
+    ConductorNotRunning,
+
+    /// The conductor could not be started or did not become ready.
+    StartupFailed,
+
+    /// A signal could not be sent to a conductor process.
+    ProcessControl,
+
+    /// Connecting to or communicating with a conductor's admin or app interface failed.
+    ConductorConnection,
+
+    /// No app interface is connected on the given port.
+    AppInterfaceNotConnected,
+
+    /// The conductor did not respond in time.
+    Timeout,
+
+    /// A file or directory on the server could not be read or written.
+    Io,
+
+    /// A DNA could not be downloaded.
+    Download,
+
+    /// An unexpected error occurred in the server.
+    Internal,
+
+    /// The error is of a kind that this version of the API does not know about.
+    #[serde(other)]
+    Unknown,
+}
+
+/// How a conductor process exited.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ConductorExitStatus {
+    /// The exit code of the process, if it exited by itself.
+    pub code: Option<i32>,
+
+    /// The signal that terminated the process, if it was terminated by a signal.
+    pub signal: Option<i32>,
+}
+
+impl std::fmt::Display for ConductorExitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exit code {}", code),
+            (None, Some(signal)) => write!(f, "signal {}", signal),
+            (None, None) => write!(f, "unknown status"),
+        }
+    }
+}
+
 /// Messages returned directly by the TryCp server, rather than relayed from Holochain
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "snake_case", tag = "type", content = "data")]
 pub enum TryCpServerResponse {
     /// See [DownloadLogsResponse].
     DownloadLogs(DownloadLogsResponse),
+
+    /// The successful response type for a [Request::ListPlayers] request.
+    ListPlayers(Vec<PlayerStatus>),
+
+    /// See [PlayerStatus].
+    PlayerStatus(PlayerStatus),
+
+    /// See [RestartResponse].
+    Restart(RestartResponse),
+
+    /// See [HelloResponse].
+    Hello(HelloResponse),
+}
+
+/// The successful response type for a [Request::Hello] request.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HelloResponse {
+    /// The [PROTOCOL_VERSION] the server was built against.
+    pub protocol_version: u32,
+    /// The version of the trycp_server crate.
+    pub server_version: String,
+    /// The `type`s of the requests the server supports.
+    pub request_types: Vec<String>,
+    /// The requested features that the server supports.
+    pub features: Vec<String>,
+    /// The version reported by the server's default holochain binary, if it could be determined.
+    pub holochain_version: Option<String>,
+}
+
+/// The successful response type for a [Request::Restart] request.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RestartResponse {
+    /// The process id of the restarted conductor.
+    pub pid: u32,
 }
 
 /// The successful response type for a [Request::DownloadLogs] request.
@@ -176,3 +524,26 @@ pub struct DownloadLogsResponse {
     /// The holochain conductor stderr log.
     pub conductor_stderr: Vec<u8>,
 }
+
+/// The successful response type for a [Request::PlayerStatus] request.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PlayerStatus {
+    /// The player id.
+    pub id: String,
+    /// Whether the player has been configured.
+    pub configured: bool,
+    /// Whether the player's conductor is running.
+    pub running: bool,
+    /// Whether the player's conductor is paused.
+    pub paused: bool,
+    /// The process id of the player's conductor, if it is running.
+    pub pid: Option<u32>,
+    /// The admin port assigned to the player, if it has been configured.
+    pub admin_port: Option<u16>,
+    /// How long the player's conductor has been running, in milliseconds.
+    pub uptime_ms: Option<u64>,
+    /// The app interface ports attached to the player's conductor.
+    pub app_ports: Vec<u16>,
+    /// How the player's conductor last exited, if it has exited.
+    pub last_exit_status: Option<ConductorExitStatus>,
+}
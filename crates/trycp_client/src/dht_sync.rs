@@ -0,0 +1,116 @@
+//! Waiting for players' DHTs to converge, like the `dhtSync` helper of the TypeScript client.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    io::Result,
+    time::Duration,
+};
+
+use holochain_conductor_api::{AdminRequest, AdminResponse};
+use holochain_types::prelude::*;
+
+use crate::Player;
+
+/// The ops a player had not integrated when [dht_sync] timed out.
+#[derive(Debug, Clone)]
+pub struct MissingOps {
+    /// The id of the player's conductor on its server.
+    pub player_id: String,
+    /// The agent of the player's cell.
+    pub agent_pub_key: AgentPubKey,
+    /// The ops other players have integrated but this player hasn't.
+    pub op_hashes: BTreeSet<DhtOpHash>,
+}
+
+/// The error of [dht_sync] when the players' DHTs did not converge in time, which can be
+/// retrieved with [std::io::Error::get_ref].
+#[derive(Debug, Clone)]
+pub struct DhtSyncTimeout {
+    /// The players that were missing ops the last time they were compared.
+    pub missing: Vec<MissingOps>,
+}
+
+impl std::fmt::Display for DhtSyncTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DHT did not sync in time:")?;
+        for missing in &self.missing {
+            write!(
+                f,
+                " {} is missing {} ops {:?};",
+                missing.player_id,
+                missing.op_hashes.len(),
+                missing.op_hashes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DhtSyncTimeout {}
+
+/// Wait until all players have integrated the same DHT ops in the cell with the given role name,
+/// comparing their full state dumps every `interval`.
+///
+/// The players can be on different servers. If they have not converged once `timeout` has passed,
+/// a [DhtSyncTimeout] with the players' missing ops is returned as the error.
+pub async fn dht_sync(
+    players: &[&Player],
+    role_name: &str,
+    interval: Duration,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let integrated = futures::future::try_join_all(
+            players
+                .iter()
+                .map(|player| integrated_op_hashes(player, role_name)),
+        )
+        .await?;
+
+        let all: HashSet<&DhtOpHash> = integrated.iter().flatten().collect();
+        let missing: Vec<_> = players
+            .iter()
+            .zip(&integrated)
+            .filter(|(_, op_hashes)| op_hashes.len() < all.len())
+            .map(|(player, op_hashes)| MissingOps {
+                player_id: player.conductor.id().to_string(),
+                agent_pub_key: player.agent_pub_key.clone(),
+                op_hashes: all
+                    .iter()
+                    .filter(|op_hash| !op_hashes.contains(op_hash))
+                    .map(|&op_hash| op_hash.clone())
+                    .collect(),
+            })
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        if tokio::time::Instant::now() + interval > deadline {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                DhtSyncTimeout { missing },
+            ));
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+async fn integrated_op_hashes(player: &Player, role_name: &str) -> Result<HashSet<DhtOpHash>> {
+    let request = AdminRequest::DumpFullState {
+        cell_id: Box::new(player.cell_id(role_name)?.clone()),
+        dht_ops_cursor: None,
+    };
+    match player.conductor.admin_request(request).await? {
+        AdminResponse::FullStateDumped(dump) => Ok(dump
+            .integration_dump
+            .integrated
+            .iter()
+            .map(DhtOpHash::with_data_sync)
+            .collect()),
+        response => Err(std::io::Error::other(format!(
+            "Unexpected admin response: {response:?}"
+        ))),
+    }
+}
@@ -0,0 +1,170 @@
+//! Typed Holochain admin and app calls, available with the `holochain` feature.
+
+use std::{collections::BTreeSet, io::Result};
+
+use ed25519_dalek::{Signer, SigningKey};
+use holochain_conductor_api::{
+    AdminRequest, AdminResponse, AppRequest, AppResponse, ZomeCallParamsSigned,
+};
+use holochain_zome_types::prelude::*;
+use rand::{rngs::OsRng, RngCore};
+
+use crate::TrycpClient;
+
+/// A key pair that is authorized to sign zome calls to a cell, like the signing credentials of
+/// the TypeScript client.
+#[derive(Clone)]
+pub struct SigningCredentials {
+    /// The secret of the capability granted to the signing key.
+    pub cap_secret: CapSecret,
+    /// The key pair that signs zome calls.
+    pub key_pair: SigningKey,
+    /// The public key of the key pair, which is the provenance of the signed zome calls.
+    pub signing_key: AgentPubKey,
+}
+
+impl SigningCredentials {
+    /// Generate a new key pair and capability secret.
+    pub fn generate() -> Self {
+        let key_pair = SigningKey::generate(&mut OsRng);
+        let signing_key = AgentPubKey::from_raw_32(key_pair.verifying_key().as_bytes().to_vec());
+        let mut cap_secret = [0; CAP_SECRET_BYTES];
+        OsRng.fill_bytes(&mut cap_secret);
+        Self {
+            cap_secret: cap_secret.into(),
+            key_pair,
+            signing_key,
+        }
+    }
+}
+
+/// A zome call to be signed with the [SigningCredentials] authorized for its cell.
+#[derive(Debug, Clone)]
+pub struct CallZomeRequest {
+    /// The cell to call.
+    pub cell_id: CellId,
+    /// The zome to call.
+    pub zome_name: ZomeName,
+    /// The function to call.
+    pub fn_name: FunctionName,
+    /// The encoded input of the function, see [ExternIO::encode].
+    pub payload: ExternIO,
+}
+
+impl TrycpClient {
+    /// Make an admin request of a player's conductor and decode its response.
+    ///
+    /// [AdminResponse::Error]s are returned as errors.
+    pub async fn admin_request(
+        &self,
+        id: impl Into<String>,
+        request: AdminRequest,
+    ) -> Result<AdminResponse> {
+        let message = rmp_serde::to_vec_named(&request).map_err(std::io::Error::other)?;
+        let response = self.call_admin_interface(id, message, None).await?;
+        match rmp_serde::from_slice(&response).map_err(std::io::Error::other)? {
+            AdminResponse::Error(error) => Err(std::io::Error::other(format!(
+                "Admin request failed: {error:?}"
+            ))),
+            response => Ok(response),
+        }
+    }
+
+    /// Make a request of a connected app interface and decode its response.
+    ///
+    /// [AppResponse::Error]s are returned as errors. Use [Self::call_zome] for zome calls, which
+    /// need to be signed.
+    pub async fn app_request(&self, port: u16, request: AppRequest) -> Result<AppResponse> {
+        let message = rmp_serde::to_vec_named(&request).map_err(std::io::Error::other)?;
+        let response = self.call_app_interface(port, message, None).await?;
+        match rmp_serde::from_slice(&response).map_err(std::io::Error::other)? {
+            AppResponse::Error(error) => Err(std::io::Error::other(format!(
+                "App request failed: {error:?}"
+            ))),
+            response => Ok(response),
+        }
+    }
+
+    /// Generate signing credentials and grant them the capability to call the given functions of
+    /// a cell of a player's conductor. Zome calls to the cell made with [Self::call_zome] are
+    /// signed with them.
+    pub async fn authorize_signing_credentials(
+        &self,
+        id: impl Into<String>,
+        cell_id: CellId,
+        functions: GrantedFunctions,
+    ) -> Result<SigningCredentials> {
+        let credentials = SigningCredentials::generate();
+        self.admin_request(
+            id,
+            AdminRequest::GrantZomeCallCapability(Box::new(GrantZomeCallCapabilityPayload {
+                cell_id: cell_id.clone(),
+                cap_grant: ZomeCallCapGrant {
+                    tag: "zome-call-signing-key".to_string(),
+                    access: CapAccess::Assigned {
+                        secret: credentials.cap_secret,
+                        assignees: BTreeSet::from([credentials.signing_key.clone()]),
+                    },
+                    functions,
+                },
+            })),
+        )
+        .await?;
+        self.set_signing_credentials(cell_id, credentials.clone());
+        Ok(credentials)
+    }
+
+    /// Sign zome calls to a cell with the given credentials, which must have been granted the
+    /// capability to call its functions.
+    pub fn set_signing_credentials(&self, cell_id: CellId, credentials: SigningCredentials) {
+        self.signing_credentials
+            .lock()
+            .unwrap()
+            .insert(cell_id, credentials);
+    }
+
+    /// Sign a zome call with the credentials authorized for its cell, make it through a connected
+    /// app interface and return the encoded output of the function.
+    pub async fn call_zome(&self, port: u16, request: CallZomeRequest) -> Result<ExternIO> {
+        let credentials = self
+            .signing_credentials
+            .lock()
+            .unwrap()
+            .get(&request.cell_id)
+            .cloned()
+            .ok_or_else(|| {
+                std::io::Error::other(format!(
+                    "No signing credentials have been authorized for cell {:?}",
+                    request.cell_id
+                ))
+            })?;
+
+        let (nonce, expires_at) =
+            holochain_nonce::fresh_nonce(Timestamp::now()).map_err(std::io::Error::other)?;
+        let params = ZomeCallParams {
+            provenance: credentials.signing_key,
+            cell_id: request.cell_id,
+            zome_name: request.zome_name,
+            fn_name: request.fn_name,
+            cap_secret: Some(credentials.cap_secret),
+            payload: request.payload,
+            nonce,
+            expires_at,
+        };
+        let (bytes, bytes_hash) = params.serialize_and_hash().map_err(std::io::Error::other)?;
+        let signature = Signature(credentials.key_pair.sign(&bytes_hash).to_bytes());
+
+        match self
+            .app_request(
+                port,
+                AppRequest::CallZome(Box::new(ZomeCallParamsSigned::new(bytes, signature))),
+            )
+            .await?
+        {
+            AppResponse::ZomeCalled(output) => Ok(*output),
+            response => Err(std::io::Error::other(format!(
+                "Unexpected response to zome call: {response:?}"
+            ))),
+        }
+    }
+}
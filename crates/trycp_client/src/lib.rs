@@ -5,17 +5,58 @@ use futures::{sink::SinkExt, stream::StreamExt};
 use std::collections::HashMap;
 use std::io::Result;
 use std::sync::Arc;
+pub use tokio_tungstenite::Connector;
 use tokio_tungstenite::{
-    tungstenite::{client::IntoClientRequest, Message},
+    tungstenite::{client::IntoClientRequest, http, Message},
     *,
 };
-pub use trycp_api::Request;
 use trycp_api::*;
+pub use trycp_api::{
+    ConductorExitStatus, DownloadLogsResponse, ErrorKind, HelloResponse, PlayerStatus,
+    ReadinessProbe, Request, RestartResponse, TryCpError,
+};
+
+#[cfg(feature = "holochain")]
+mod dht_sync;
+#[cfg(feature = "holochain")]
+mod holochain;
+mod requests;
+#[cfg(feature = "holochain")]
+mod scenario;
+
+#[cfg(feature = "holochain")]
+pub use dht_sync::{dht_sync, DhtSyncTimeout, MissingOps};
+#[cfg(feature = "holochain")]
+pub use holochain::{CallZomeRequest, SigningCredentials};
+#[cfg(feature = "holochain")]
+pub use scenario::{add_all_agents_to_all_conductors, AppSource, Conductor, Player, Scenario};
+
+/// How long to wait for the server to answer the [Request::Hello] sent on connect.
+const HELLO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How long the typed request methods wait for a response by default.
+pub const DEFAULT_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
 
 type WsCore = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
 type WsSink = futures::stream::SplitSink<WsCore, Message>;
 type Ws = Arc<tokio::sync::Mutex<WsSink>>;
 
+/// Build a TLS connector that trusts servers with certificates issued by the given PEM encoded CA
+/// certificates, for example a self-signed certificate of a TryCP server.
+pub fn tls_connector(ca_certs_pem: &[u8]) -> Result<Connector> {
+    let mut root_store = rustls::RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut &*ca_certs_pem) {
+        root_store.add(cert?).map_err(std::io::Error::other)?;
+    }
+    if root_store.is_empty() {
+        return Err(std::io::Error::other("No CA certificates found"));
+    }
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    Ok(Connector::Rustls(Arc::new(config)))
+}
+
 /// Signal emitted from a conductor.
 pub struct Signal {
     /// The app port from which this signal was emitted.
@@ -25,6 +66,26 @@ pub struct Signal {
     pub data: Vec<u8>,
 }
 
+/// Notification that a conductor exited without having been shut down through the trycp server.
+#[derive(Debug, Clone)]
+pub struct ConductorExit {
+    /// The id of the player whose conductor exited.
+    pub id: String,
+
+    /// How the conductor exited.
+    pub status: ConductorExitStatus,
+}
+
+/// Signal emitted by a conductor on its admin interface.
+#[derive(Debug, Clone)]
+pub struct AdminSignal {
+    /// The id of the player whose conductor emitted this signal.
+    pub id: String,
+
+    /// The content of the signal.
+    pub data: Vec<u8>,
+}
+
 /// Trycp client recv.
 pub struct SignalRecv(tokio::sync::mpsc::Receiver<Signal>);
 
@@ -41,6 +102,15 @@ pub struct TrycpClient {
     pend:
         Arc<std::sync::Mutex<HashMap<u64, tokio::sync::oneshot::Sender<Result<MessageResponse>>>>>,
     recv_task: tokio::task::JoinHandle<()>,
+    conductor_exits: tokio::sync::broadcast::Sender<ConductorExit>,
+    admin_signals: tokio::sync::broadcast::Sender<AdminSignal>,
+    server_info: HelloResponse,
+    namespace: Option<String>,
+    request_timeout: std::time::Duration,
+    #[cfg(feature = "holochain")]
+    signing_credentials: std::sync::Mutex<
+        HashMap<holochain_zome_types::prelude::CellId, holochain::SigningCredentials>,
+    >,
 }
 
 impl Drop for TrycpClient {
@@ -55,13 +125,28 @@ impl Drop for TrycpClient {
 
 impl TrycpClient {
     /// Connect to a remote trycp server.
+    ///
+    /// Fails if the server speaks a different protocol version than this client.
     pub async fn connect<R>(request: R) -> Result<(Self, SignalRecv)>
     where
         R: IntoClientRequest + Unpin,
     {
-        let (w, _) = tokio_tungstenite::connect_async(request)
-            .await
-            .map_err(std::io::Error::other)?;
+        Self::connect_with_connector(request, None).await
+    }
+
+    /// Connect to a remote trycp server, using the given connector for `wss://` URLs instead of
+    /// one that trusts the system's root certificates. See [tls_connector] to trust a custom CA.
+    pub async fn connect_with_connector<R>(
+        request: R,
+        connector: Option<Connector>,
+    ) -> Result<(Self, SignalRecv)>
+    where
+        R: IntoClientRequest + Unpin,
+    {
+        let (w, _) =
+            tokio_tungstenite::connect_async_tls_with_config(request, None, false, connector)
+                .await
+                .map_err(std::io::Error::other)?;
 
         let (sink, mut stream) = w.split();
 
@@ -70,10 +155,14 @@ impl TrycpClient {
         let pend = Arc::new(std::sync::Mutex::new(map));
 
         let (recv_send, recv_recv) = tokio::sync::mpsc::channel(32);
+        let (conductor_exits, _) = tokio::sync::broadcast::channel(32);
+        let (admin_signals, _) = tokio::sync::broadcast::channel(32);
 
         let ws = Arc::new(tokio::sync::Mutex::new(sink));
         let ws2 = ws.clone();
         let pend2 = pend.clone();
+        let conductor_exits2 = conductor_exits.clone();
+        let admin_signals2 = admin_signals.clone();
         let recv_task = tokio::task::spawn(async move {
             while let Some(Ok(msg)) = stream.next().await {
                 let msg = match msg {
@@ -107,21 +196,141 @@ impl TrycpClient {
                             let _ = resp.send(response.map_err(std::io::Error::other));
                         }
                     }
+                    MessageToClient::ConductorExited { id, status } => {
+                        let _ = conductor_exits2.send(ConductorExit { id, status });
+                    }
+                    MessageToClient::AdminSignal { id, data } => {
+                        let _ = admin_signals2.send(AdminSignal { id, data });
+                    }
                 }
             }
+
+            for (_, resp) in pend2.lock().unwrap().drain() {
+                let _ = resp.send(Err(std::io::Error::other(
+                    "Connection to TryCP server closed",
+                )));
+            }
         });
 
-        Ok((
-            Self {
-                ws,
-                pend,
-                recv_task,
+        let mut client = Self {
+            ws,
+            pend,
+            recv_task,
+            conductor_exits,
+            admin_signals,
+            server_info: HelloResponse {
+                protocol_version: PROTOCOL_VERSION,
+                server_version: String::new(),
+                request_types: Vec::new(),
+                features: Vec::new(),
+                holochain_version: None,
             },
-            SignalRecv(recv_recv),
-        ))
+            namespace: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            #[cfg(feature = "holochain")]
+            signing_credentials: Default::default(),
+        };
+        client.server_info = client.hello().await?;
+
+        Ok((client, SignalRecv(recv_recv)))
+    }
+
+    /// Connect to a remote trycp server that requires authentication, presenting the token as a
+    /// bearer token in the websocket upgrade request. To also trust a custom CA, add the
+    /// `Authorization: Bearer <token>` header to the request and use [Self::connect_with_connector].
+    pub async fn connect_with_auth_token<R>(request: R, token: &str) -> Result<(Self, SignalRecv)>
+    where
+        R: IntoClientRequest + Unpin,
+    {
+        let mut request = request
+            .into_client_request()
+            .map_err(std::io::Error::other)?;
+        request.headers_mut().insert(
+            http::header::AUTHORIZATION,
+            format!("Bearer {token}")
+                .parse()
+                .map_err(std::io::Error::other)?,
+        );
+        Self::connect(request).await
+    }
+
+    async fn hello(&self) -> Result<HelloResponse> {
+        let response = self
+            .request(
+                Request::Hello {
+                    client_version: PROTOCOL_VERSION,
+                    features: FEATURES.iter().map(ToString::to_string).collect(),
+                },
+                HELLO_TIMEOUT,
+            )
+            .await
+            .map_err(|err| {
+                if err.get_ref().is_some_and(|err| err.is::<TryCpError>()) {
+                    err
+                } else {
+                    std::io::Error::other(format!(
+                        "TryCP server did not answer the protocol handshake, it may predate protocol version {}: {}",
+                        PROTOCOL_VERSION, err
+                    ))
+                }
+            })?;
+
+        let server_info = match rmp_serde::from_slice(&response.into_bytes()) {
+            Ok(TryCpServerResponse::Hello(server_info)) => server_info,
+            Ok(response) => {
+                return Err(std::io::Error::other(format!(
+                    "Unexpected response to protocol handshake: {response:?}"
+                )))
+            }
+            Err(err) => return Err(std::io::Error::other(err)),
+        };
+        if server_info.protocol_version != PROTOCOL_VERSION {
+            return Err(std::io::Error::other(format!(
+                "TryCP server speaks protocol version {} but this client speaks version {}",
+                server_info.protocol_version, PROTOCOL_VERSION
+            )));
+        }
+        Ok(server_info)
+    }
+
+    /// Scope all further requests to the players in the given namespace, so that several test
+    /// runs can share a server. Player ids only need to be unique within a namespace, and
+    /// [Request::Reset] and [Request::ListPlayers] only apply to the namespace.
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    /// Wait the given time for responses to the typed request methods, such as
+    /// [Self::configure_player], instead of [DEFAULT_REQUEST_TIMEOUT]. Requests with their own
+    /// `timeout_ms` wait at least a little longer than that.
+    pub fn with_request_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Information about the server, as reported when connecting.
+    pub fn server_info(&self) -> &HelloResponse {
+        &self.server_info
+    }
+
+    /// Subscribe to notifications about conductors that exited without having been shut down
+    /// through the trycp server.
+    pub fn subscribe_conductor_exits(&self) -> tokio::sync::broadcast::Receiver<ConductorExit> {
+        self.conductor_exits.subscribe()
+    }
+
+    /// Subscribe to signals emitted by conductors on their admin interfaces. Signals are only
+    /// received from conductors whose admin interface the server has connected to, which it does
+    /// on the first admin call.
+    pub fn subscribe_admin_signals(&self) -> tokio::sync::broadcast::Receiver<AdminSignal> {
+        self.admin_signals.subscribe()
     }
 
     /// Make a request of the trycp server.
+    ///
+    /// Errors reported by the server are returned as an [std::io::Error] wrapping a
+    /// [TryCpError], which can be retrieved with [std::io::Error::get_ref].
     pub async fn request(
         &self,
         request: Request,
@@ -140,7 +349,12 @@ impl TrycpClient {
             pend.lock().unwrap().remove(&mid);
         });
 
-        let request = RequestWrapper { id: mid, request };
+        let request = RequestWrapper {
+            id: mid,
+            request,
+            structured_errors: true,
+            namespace: self.namespace.clone(),
+        };
 
         let request = rmp_serde::to_vec_named(&request).map_err(std::io::Error::other)?;
 
@@ -0,0 +1,254 @@
+//! Typed methods for every [Request], decoding the server's responses.
+
+use std::{io::Result, time::Duration};
+
+use trycp_api::*;
+
+use crate::TrycpClient;
+
+/// How much longer than a request's own `timeout_ms` to wait for the server's response, so that
+/// the server's timeout error arrives before the client gives up.
+const TIMEOUT_MARGIN: Duration = Duration::from_secs(5);
+
+impl TrycpClient {
+    /// Authenticate the connection with one of the tokens the server accepts.
+    pub async fn authenticate(&self, token: impl Into<String>) -> Result<()> {
+        self.request_unit(Request::Authenticate {
+            token: token.into(),
+        })
+        .await
+    }
+
+    /// Store a DNA file on the server under the given file name and return its path on the
+    /// server.
+    pub async fn save_dna(&self, id: impl Into<String>, content: Vec<u8>) -> Result<String> {
+        self.request_path(Request::SaveDna {
+            id: id.into(),
+            content,
+        })
+        .await
+    }
+
+    /// Have the server download a DNA file and return its path on the server.
+    pub async fn download_dna(&self, url: impl Into<String>) -> Result<String> {
+        self.request_path(Request::DownloadDna { url: url.into() })
+            .await
+    }
+
+    /// Set up a player. See [Request::ConfigurePlayer].
+    pub async fn configure_player(
+        &self,
+        id: impl Into<String>,
+        partial_config: impl Into<String>,
+        holochain_version: Option<String>,
+    ) -> Result<()> {
+        self.request_unit(Request::ConfigurePlayer {
+            id: id.into(),
+            partial_config: partial_config.into(),
+            holochain_version,
+        })
+        .await
+    }
+
+    /// Start a player's conductor. See [Request::Startup].
+    pub async fn startup(
+        &self,
+        id: impl Into<String>,
+        log_level: Option<String>,
+        timeout_ms: Option<u64>,
+        readiness_probe: Option<ReadinessProbe>,
+    ) -> Result<()> {
+        self.request(
+            Request::Startup {
+                id: id.into(),
+                log_level,
+                timeout_ms,
+                readiness_probe,
+            },
+            self.timeout_for(timeout_ms),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Shut down a player's conductor, by default with SIGTERM.
+    pub async fn shutdown(&self, id: impl Into<String>, signal: Option<String>) -> Result<()> {
+        self.request_unit(Request::Shutdown {
+            id: id.into(),
+            signal,
+        })
+        .await
+    }
+
+    /// Shut down a player's conductor and start it up again, keeping its data. See
+    /// [Request::Restart].
+    pub async fn restart(
+        &self,
+        id: impl Into<String>,
+        signal: Option<String>,
+        log_level: Option<String>,
+        timeout_ms: Option<u64>,
+        readiness_probe: Option<ReadinessProbe>,
+    ) -> Result<RestartResponse> {
+        let response = self
+            .request(
+                Request::Restart {
+                    id: id.into(),
+                    signal,
+                    log_level,
+                    timeout_ms,
+                    readiness_probe,
+                },
+                self.timeout_for(timeout_ms),
+            )
+            .await?;
+        match rmp_serde::from_slice(&response.into_bytes()).map_err(std::io::Error::other)? {
+            TryCpServerResponse::Restart(response) => Ok(response),
+            response => Err(unexpected_response(response)),
+        }
+    }
+
+    /// Freeze a player's conductor with SIGSTOP.
+    pub async fn pause(&self, id: impl Into<String>) -> Result<()> {
+        self.request_unit(Request::Pause { id: id.into() }).await
+    }
+
+    /// Thaw a paused player's conductor with SIGCONT.
+    pub async fn resume(&self, id: impl Into<String>) -> Result<()> {
+        self.request_unit(Request::Resume { id: id.into() }).await
+    }
+
+    /// Shut down a player's conductor and remove the player, optionally keeping its directory.
+    pub async fn remove_player(&self, id: impl Into<String>, keep_data: bool) -> Result<()> {
+        self.request_unit(Request::RemovePlayer {
+            id: id.into(),
+            keep_data,
+        })
+        .await
+    }
+
+    /// Remove all players, or all players of the client's namespace.
+    pub async fn reset(&self) -> Result<()> {
+        self.request_unit(Request::Reset).await
+    }
+
+    /// Send an encoded admin request to a player's conductor and return the encoded response.
+    pub async fn call_admin_interface(
+        &self,
+        id: impl Into<String>,
+        message: Vec<u8>,
+        timeout_ms: Option<u64>,
+    ) -> Result<Vec<u8>> {
+        self.request(
+            Request::CallAdminInterface {
+                id: id.into(),
+                message,
+                timeout_ms,
+            },
+            self.timeout_for(timeout_ms),
+        )
+        .await
+        .map(MessageResponse::into_bytes)
+    }
+
+    /// Connect to a conductor's app interface, so that app calls can be made and signals are
+    /// received.
+    pub async fn connect_app_interface(&self, token: Vec<u8>, port: u16) -> Result<()> {
+        self.request_unit(Request::ConnectAppInterface { token, port })
+            .await
+    }
+
+    /// Disconnect from a conductor's app interface.
+    pub async fn disconnect_app_interface(&self, port: u16) -> Result<()> {
+        self.request_unit(Request::DisconnectAppInterface { port })
+            .await
+    }
+
+    /// Send an encoded app request to a connected app interface and return the encoded response.
+    pub async fn call_app_interface(
+        &self,
+        port: u16,
+        message: Vec<u8>,
+        timeout_ms: Option<u64>,
+    ) -> Result<Vec<u8>> {
+        self.request(
+            Request::CallAppInterface {
+                port,
+                message,
+                timeout_ms,
+            },
+            self.timeout_for(timeout_ms),
+        )
+        .await
+        .map(MessageResponse::into_bytes)
+    }
+
+    /// Download the logs of a player's conductor.
+    pub async fn download_logs(&self, id: impl Into<String>) -> Result<DownloadLogsResponse> {
+        match self
+            .request_server_response(Request::DownloadLogs { id: id.into() })
+            .await?
+        {
+            TryCpServerResponse::DownloadLogs(response) => Ok(response),
+            response => Err(unexpected_response(response)),
+        }
+    }
+
+    /// The status of all players, or all players of the client's namespace.
+    pub async fn list_players(&self) -> Result<Vec<PlayerStatus>> {
+        match self.request_server_response(Request::ListPlayers).await? {
+            TryCpServerResponse::ListPlayers(players) => Ok(players),
+            response => Err(unexpected_response(response)),
+        }
+    }
+
+    /// The status of a player.
+    pub async fn player_status(&self, id: impl Into<String>) -> Result<PlayerStatus> {
+        match self
+            .request_server_response(Request::PlayerStatus { id: id.into() })
+            .await?
+        {
+            TryCpServerResponse::PlayerStatus(status) => Ok(status),
+            response => Err(unexpected_response(response)),
+        }
+    }
+
+    /// How long to wait for the response to a request with the given `timeout_ms`.
+    fn timeout_for(&self, timeout_ms: Option<u64>) -> Duration {
+        match timeout_ms {
+            Some(timeout_ms) => self
+                .request_timeout
+                .max(Duration::from_millis(timeout_ms) + TIMEOUT_MARGIN),
+            None => self.request_timeout,
+        }
+    }
+
+    async fn request_unit(&self, request: Request) -> Result<()> {
+        self.request(request, self.request_timeout)
+            .await
+            .map(|_| ())
+    }
+
+    async fn request_path(&self, request: Request) -> Result<String> {
+        match self.request(request, self.request_timeout).await? {
+            MessageResponse::Text(path) => Ok(path),
+            response => Err(std::io::Error::other(format!(
+                "Unexpected response from TryCP server: {response:?}"
+            ))),
+        }
+    }
+
+    pub(crate) async fn request_server_response(
+        &self,
+        request: Request,
+    ) -> Result<TryCpServerResponse> {
+        let response = self.request(request, self.request_timeout).await?;
+        rmp_serde::from_slice(&response.into_bytes()).map_err(std::io::Error::other)
+    }
+}
+
+fn unexpected_response(response: TryCpServerResponse) -> std::io::Error {
+    std::io::Error::other(format!(
+        "Unexpected response from TryCP server: {response:?}"
+    ))
+}
@@ -0,0 +1,382 @@
+//! Scenarios of players with installed hApps, like the `TryCpScenario` of the TypeScript client.
+
+use std::{
+    collections::HashMap,
+    io::Result,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use holochain_conductor_api::{
+    AdminRequest, AdminResponse, AppInfo, CellInfo, IssueAppAuthenticationTokenPayload,
+};
+use holochain_types::{
+    app::{AppBundleSource, InstallAppPayload},
+    websocket::AllowedOrigins,
+};
+use holochain_zome_types::prelude::*;
+use rand::RngCore;
+use tokio::runtime::RuntimeFlavor;
+
+use crate::{CallZomeRequest, TrycpClient};
+
+/// Where to get a hApp bundle from.
+#[derive(Debug, Clone)]
+pub enum AppSource {
+    /// A hApp file on the client's machine, which is uploaded to the server.
+    Path(PathBuf),
+    /// A file or web URL that the server downloads the hApp from.
+    Url(String),
+}
+
+/// A set of players on a TryCP server. Players are added with a conductor each, and all players'
+/// apps are installed with the same network seed.
+///
+/// Players are added in the namespace of the client, or in a random namespace if the client has
+/// none, so that scenarios of several users can share a server. Call [Scenario::cleanup] once
+/// done, which resets that namespace and so removes all of its players. A scenario that is dropped
+/// without cleanup resets the namespace while it is dropped, but only on a multi-threaded tokio
+/// runtime, because the reset has to block the dropping thread. On a current-thread runtime, such
+/// as that of a default `#[tokio::test]`, a warning is printed instead and the players are left on
+/// the server.
+pub struct Scenario {
+    client: Arc<TrycpClient>,
+    partial_config: String,
+    network_seed: String,
+    next_conductor: AtomicUsize,
+    cleaned_up: bool,
+}
+
+impl Scenario {
+    /// Create a scenario on the server the client is connected to, with a random network seed.
+    /// A client without a namespace is given a random one.
+    pub fn new(client: TrycpClient) -> Self {
+        let client = match client.namespace {
+            Some(_) => client,
+            None => client.with_namespace(format!("scenario-{}", random_hex())),
+        };
+        Self {
+            client: Arc::new(client),
+            partial_config: String::new(),
+            network_seed: random_hex(),
+            next_conductor: AtomicUsize::new(1),
+            cleaned_up: false,
+        }
+    }
+
+    /// Configure conductors with the given partial config. See [crate::Request::ConfigurePlayer].
+    pub fn with_partial_config(mut self, partial_config: impl Into<String>) -> Self {
+        self.partial_config = partial_config.into();
+        self
+    }
+
+    /// Install apps with the given network seed instead of a random one.
+    pub fn with_network_seed(mut self, network_seed: impl Into<String>) -> Self {
+        self.network_seed = network_seed.into();
+        self
+    }
+
+    /// The client the scenario makes its requests with.
+    pub fn client(&self) -> &TrycpClient {
+        &self.client
+    }
+
+    /// The namespace the scenario's players are added in.
+    pub fn namespace(&self) -> &str {
+        self.client
+            .namespace
+            .as_deref()
+            .expect("scenario clients always have a namespace")
+    }
+
+    /// The network seed apps are installed with.
+    pub fn network_seed(&self) -> &str {
+        &self.network_seed
+    }
+
+    /// Configure and start up a conductor.
+    pub async fn add_conductor(&self) -> Result<Conductor> {
+        let id = format!(
+            "conductor-{}",
+            self.next_conductor.fetch_add(1, Ordering::Relaxed)
+        );
+        self.client
+            .configure_player(&id, &self.partial_config, None)
+            .await?;
+        self.client.startup(&id, None, None, None).await?;
+        Ok(Conductor {
+            id,
+            client: Arc::clone(&self.client),
+            network_seed: self.network_seed.clone(),
+        })
+    }
+
+    /// Add a conductor with the hApp installed for a new agent.
+    pub async fn add_player_with_app(&self, source: &AppSource) -> Result<Player> {
+        self.add_conductor().await?.install_app(source).await
+    }
+
+    /// Add a conductor for each of the hApps, with the hApp installed for a new agent.
+    pub async fn add_players_with_apps(&self, sources: &[AppSource]) -> Result<Vec<Player>> {
+        futures::future::try_join_all(
+            sources
+                .iter()
+                .map(|source| self.add_player_with_app(source)),
+        )
+        .await
+    }
+
+    /// Reset the scenario's namespace on the server and wait for it to finish.
+    pub async fn cleanup(mut self) -> Result<()> {
+        self.cleaned_up = true;
+        self.client.reset().await
+    }
+}
+
+impl Drop for Scenario {
+    fn drop(&mut self) {
+        if self.cleaned_up {
+            return;
+        }
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) if runtime.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                if let Err(e) =
+                    tokio::task::block_in_place(|| runtime.block_on(self.client.reset()))
+                {
+                    eprintln!("Could not reset TryCP server after scenario: {}", e);
+                }
+            }
+            _ => eprintln!(
+                "warn: Scenario was dropped without cleanup outside of a multi-threaded tokio runtime, so its players are left on the TryCP server. Call Scenario::cleanup to remove them."
+            ),
+        }
+    }
+}
+
+/// A running conductor of a [Scenario].
+#[derive(Clone)]
+pub struct Conductor {
+    id: String,
+    client: Arc<TrycpClient>,
+    network_seed: String,
+}
+
+impl Conductor {
+    /// The id of the conductor's player on the server.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The client the conductor is managed with.
+    pub fn client(&self) -> &TrycpClient {
+        &self.client
+    }
+
+    /// Make an admin request of the conductor.
+    pub async fn admin_request(&self, request: AdminRequest) -> Result<AdminResponse> {
+        self.client.admin_request(&self.id, request).await
+    }
+
+    /// Install the hApp for a new agent, enable it, attach and connect an app interface and
+    /// authorize signing credentials for all of its cells.
+    pub async fn install_app(&self, source: &AppSource) -> Result<Player> {
+        let path = match source {
+            AppSource::Path(path) => {
+                let file_name = path
+                    .file_name()
+                    .and_then(|file_name| file_name.to_str())
+                    .ok_or_else(|| {
+                        std::io::Error::other(format!("Invalid hApp path {}", path.display()))
+                    })?;
+                let content = tokio::fs::read(path).await?;
+                self.client.save_dna(file_name, content).await?
+            }
+            AppSource::Url(url) => self.client.download_dna(url).await?,
+        };
+
+        let agent_pub_key = match self
+            .admin_request(AdminRequest::GenerateAgentPubKey)
+            .await?
+        {
+            AdminResponse::AgentPubKeyGenerated(agent_pub_key) => agent_pub_key,
+            response => return Err(unexpected_response(response)),
+        };
+
+        let installed_app_id = format!("{}-app", self.id);
+        let request = AdminRequest::InstallApp(Box::new(InstallAppPayload {
+            source: AppBundleSource::Path(path.into()),
+            agent_key: Some(agent_pub_key),
+            installed_app_id: Some(installed_app_id.clone()),
+            network_seed: Some(self.network_seed.clone()),
+            roles_settings: None,
+            ignore_genesis_failure: false,
+            allow_throwaway_random_agent_key: false,
+        }));
+        match self.admin_request(request).await? {
+            AdminResponse::AppInstalled(_) => {}
+            response => return Err(unexpected_response(response)),
+        }
+
+        let app_info = match self
+            .admin_request(AdminRequest::EnableApp {
+                installed_app_id: installed_app_id.clone(),
+            })
+            .await?
+        {
+            AdminResponse::AppEnabled { app, errors } if errors.is_empty() => app,
+            AdminResponse::AppEnabled { errors, .. } => {
+                return Err(std::io::Error::other(format!(
+                    "Could not enable app {installed_app_id}: {errors:?}"
+                )))
+            }
+            response => return Err(unexpected_response(response)),
+        };
+
+        let app_port = match self
+            .admin_request(AdminRequest::AttachAppInterface {
+                port: None,
+                allowed_origins: AllowedOrigins::Any,
+                installed_app_id: Some(installed_app_id.clone()),
+            })
+            .await?
+        {
+            AdminResponse::AppInterfaceAttached { port } => port,
+            response => return Err(unexpected_response(response)),
+        };
+
+        let token = match self
+            .admin_request(AdminRequest::IssueAppAuthenticationToken(
+                IssueAppAuthenticationTokenPayload::for_installed_app_id(installed_app_id),
+            ))
+            .await?
+        {
+            AdminResponse::AppAuthenticationTokenIssued(issued) => issued.token,
+            response => return Err(unexpected_response(response)),
+        };
+        self.client.connect_app_interface(token, app_port).await?;
+
+        let mut cells = HashMap::new();
+        for (role_name, cell_infos) in &app_info.cell_info {
+            for cell_info in cell_infos {
+                let (name, cell_id) = match cell_info {
+                    CellInfo::Provisioned(cell) => (role_name.clone(), cell.cell_id.clone()),
+                    CellInfo::Cloned(cell) => (cell.clone_id.to_string(), cell.cell_id.clone()),
+                    CellInfo::Stem(_) => continue,
+                };
+                self.client
+                    .authorize_signing_credentials(&self.id, cell_id.clone(), GrantedFunctions::All)
+                    .await?;
+                cells.insert(name, cell_id);
+            }
+        }
+
+        Ok(Player {
+            conductor: self.clone(),
+            agent_pub_key: app_info.agent_pub_key.clone(),
+            app_info,
+            app_port,
+            cells,
+        })
+    }
+}
+
+/// Add the agent info of every conductor to every other conductor's peer store, so that their
+/// agents can find each other without a bootstrap service.
+///
+/// The conductors can be on different servers.
+pub async fn add_all_agents_to_all_conductors(conductors: &[&Conductor]) -> Result<()> {
+    let agent_infos = futures::future::try_join_all(conductors.iter().map(|conductor| async {
+        match conductor
+            .admin_request(AdminRequest::AgentInfo { cell_id: None })
+            .await?
+        {
+            AdminResponse::AgentInfo(agent_infos) => Ok(agent_infos),
+            response => Err(unexpected_response(response)),
+        }
+    }))
+    .await?;
+
+    futures::future::try_join_all(conductors.iter().enumerate().map(|(i, conductor)| {
+        let agent_infos = agent_infos
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .flat_map(|(_, agent_infos)| agent_infos.iter().cloned())
+            .collect();
+        async move {
+            match conductor
+                .admin_request(AdminRequest::AddAgentInfo { agent_infos })
+                .await?
+            {
+                AdminResponse::AgentInfoAdded => Ok(()),
+                response => Err(unexpected_response(response)),
+            }
+        }
+    }))
+    .await?;
+    Ok(())
+}
+
+/// An agent with an installed hApp on a [Conductor].
+pub struct Player {
+    /// The conductor the hApp is installed on.
+    pub conductor: Conductor,
+    /// The agent the hApp is installed for.
+    pub agent_pub_key: AgentPubKey,
+    /// The installed hApp.
+    pub app_info: AppInfo,
+    /// The port of the app interface the client is connected to.
+    pub app_port: u16,
+    /// The cells of the hApp by role name, or by clone id for clone cells.
+    pub cells: HashMap<RoleName, CellId>,
+}
+
+impl Player {
+    /// The cell with the given role name or clone id.
+    pub fn cell_id(&self, role_name: &str) -> Result<&CellId> {
+        self.cells
+            .get(role_name)
+            .ok_or_else(|| std::io::Error::other(format!("No cell with role name {role_name}")))
+    }
+
+    /// Call a zome function of the cell with the given role name or clone id and decode its
+    /// output.
+    pub async fn call_zome<I, O>(
+        &self,
+        role_name: &str,
+        zome_name: impl Into<ZomeName>,
+        fn_name: impl Into<FunctionName>,
+        payload: I,
+    ) -> Result<O>
+    where
+        I: serde::Serialize + std::fmt::Debug,
+        O: serde::de::DeserializeOwned + std::fmt::Debug,
+    {
+        let request = CallZomeRequest {
+            cell_id: self.cell_id(role_name)?.clone(),
+            zome_name: zome_name.into(),
+            fn_name: fn_name.into(),
+            payload: ExternIO::encode(payload).map_err(std::io::Error::other)?,
+        };
+        self.conductor
+            .client
+            .call_zome(self.app_port, request)
+            .await?
+            .decode()
+            .map_err(std::io::Error::other)
+    }
+}
+
+/// 16 random bytes in hexadecimal.
+fn random_hex() -> String {
+    let mut bytes = [0; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn unexpected_response(response: AdminResponse) -> std::io::Error {
+    std::io::Error::other(format!("Unexpected admin response: {response:?}"))
+}
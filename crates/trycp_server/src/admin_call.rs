@@ -1,11 +1,16 @@
-use crate::{HolochainMessage, WsClientDuplex, PLAYERS};
-use futures::lock::Mutex;
-use futures::{SinkExt, StreamExt};
+use crate::{
+    broadcast_player_event, HolochainMessage, MessageToClient, ReportError, WsReader,
+    WsRequestWriter, PLAYERS,
+};
+use futures::{SinkExt, StreamExt, TryStreamExt};
 use once_cell::sync::Lazy;
-use snafu::{OptionExt, ResultExt, Snafu};
+use snafu::{IntoError, OptionExt, ResultExt, Snafu};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpStream;
+use tokio::sync::oneshot;
 use tokio::time::error::Elapsed;
 use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
 use tokio_tungstenite::tungstenite::protocol::CloseFrame;
@@ -13,9 +18,31 @@ use tokio_tungstenite::tungstenite::{
     self, client::IntoClientRequest, protocol::WebSocketConfig, Message,
 };
 use tokio_tungstenite::WebSocketStream;
+use trycp_api::ErrorKind;
+
+/// A connection to a conductor's admin interface. Calls can be made concurrently; a listen task
+/// routes each of Holochain's responses to the call with the matching request id.
+pub(crate) struct AdminConnection {
+    request_writer: Arc<futures::lock::Mutex<WsRequestWriter>>,
+    pending_requests: PendingRequests,
+    /// The id of the next request sent to Holochain. Ids are never reused, so that a late response
+    /// to a call that timed out can't be taken for the response to a later call.
+    next_request_id: AtomicU64,
+    listen_task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for AdminConnection {
+    fn drop(&mut self) {
+        self.listen_task.abort();
+    }
+}
+
+/// Calls to the admin interface that are waiting for Holochain's response, keyed by the request
+/// id sent to Holochain.
+type PendingRequests = Arc<parking_lot::Mutex<HashMap<u64, oneshot::Sender<Vec<u8>>>>>;
 
 pub(crate) static ADMIN_CONNECTIONS: Lazy<
-    futures::lock::Mutex<HashMap<String, Arc<futures::lock::Mutex<WebSocketStream<TcpStream>>>>>,
+    futures::lock::Mutex<HashMap<String, Arc<AdminConnection>>>,
 > = Lazy::new(Default::default);
 
 #[derive(Debug, Snafu)]
@@ -30,132 +57,244 @@ pub(crate) enum AdminCallError {
     Call { source: CallError },
 }
 
-pub(crate) async fn admin_call(id: String, message: Vec<u8>) -> Result<Vec<u8>, AdminCallError> {
+impl ReportError for AdminCallError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::PlayerNotConfigured { .. } => ErrorKind::PlayerNotConfigured,
+            Self::TcpConnect { .. } | Self::WsConnect { .. } => ErrorKind::ConductorConnection,
+            Self::Call {
+                source: CallError::ResponseTimeout { .. },
+            } => ErrorKind::Timeout,
+            Self::Call { .. } => ErrorKind::ConductorConnection,
+        }
+    }
+}
+
+pub(crate) async fn admin_call(
+    id: String,
+    message: Vec<u8>,
+    timeout: Duration,
+) -> Result<Vec<u8>, AdminCallError> {
     println!("admin_interface_call id: {:?}", id);
 
-    let mut admin_connections = ADMIN_CONNECTIONS.lock().await;
-    if !admin_connections.contains_key(&id) {
-        let port = PLAYERS
-            .read()
-            .get(&id)
-            .map(|player| player.admin_port)
-            .context(PlayerNotConfigured { id: id.clone() })?;
-
-        let stream = tokio::net::TcpStream::connect(("localhost", port))
-            .await
-            .context(TcpConnect)?;
-
-        let uri = format!("ws://localhost:{}", port);
-        let mut request = uri.clone().into_client_request().expect("not a valid URI");
-        // needed for admin websocket connection to be accepted
-        request.headers_mut().insert(
-            "origin",
-            "trycp-admin".parse().expect("invalid origin header value"),
-        );
-        request.body();
-
-        println!("Establishing admin interface with {:?}", uri);
-
-        let (ws_stream, _) = tokio_tungstenite::client_async_with_config(
-            request,
-            stream,
-            Some(WebSocketConfig::default()),
-        )
-        .await
-        .context(WsConnect)?;
+    let connection = ensure_connected(&id).await?;
 
-        println!("Established admin interface");
+    let response = call(&connection, message, timeout).await?;
 
-        admin_connections.insert(id.clone(), Arc::new(futures::lock::Mutex::new(ws_stream)));
+    if let Ok(AttachedAppInterface::AppInterfaceAttached { port }) =
+        rmp_serde::from_slice(&response)
+    {
+        if let Some(player) = PLAYERS.read().get(&id) {
+            player.app_ports.lock().insert(port);
+        }
     }
 
-    let mut ws_stream = admin_connections[&id].lock().await;
+    Ok(response)
+}
 
-    let call_result = call(&mut ws_stream, 0, message).await;
+/// The part of Holochain's admin response to attaching an app interface that trycp keeps track
+/// of, so that the app interface can be associated with the player.
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+enum AttachedAppInterface {
+    AppInterfaceAttached { port: u16 },
+}
+
+/// Connect to a player's admin interface, unless an open connection to it already exists. The
+/// global connection map is not locked while connecting, so that calls to other players aren't
+/// held up.
+pub(crate) async fn ensure_connected(id: &str) -> Result<Arc<AdminConnection>, AdminCallError> {
+    if let Some(connection) = ADMIN_CONNECTIONS.lock().await.get(id) {
+        if !connection.listen_task.is_finished() {
+            return Ok(Arc::clone(connection));
+        }
+    }
 
-    Ok(call_result?)
+    let port = PLAYERS
+        .read()
+        .get(id)
+        .map(|player| player.admin_port)
+        .context(PlayerNotConfigured { id })?;
+
+    println!("Establishing admin interface with ws://localhost:{}", port);
+    let (request_writer, reader) = connect(port).await?.split();
+    println!("Established admin interface");
+    let request_writer = Arc::new(futures::lock::Mutex::new(request_writer));
+    let pending_requests = PendingRequests::default();
+    let listen_task = tokio::task::spawn(listen(
+        id.to_string(),
+        reader,
+        Arc::clone(&request_writer),
+        Arc::clone(&pending_requests),
+    ));
+    let connection = Arc::new(AdminConnection {
+        request_writer,
+        pending_requests,
+        next_request_id: AtomicU64::new(0),
+        listen_task,
+    });
+
+    let mut admin_connections = ADMIN_CONNECTIONS.lock().await;
+    match admin_connections.get(id) {
+        // Another call connected in the meantime.
+        Some(existing) if !existing.listen_task.is_finished() => Ok(Arc::clone(existing)),
+        _ => {
+            admin_connections.insert(id.to_string(), Arc::clone(&connection));
+            Ok(connection)
+        }
+    }
+}
+
+/// Open a websocket connection to the admin interface on the given port. Nothing is logged, so
+/// that the interface can be polled with it until it accepts connections.
+pub(crate) async fn connect(port: u16) -> Result<WebSocketStream<TcpStream>, AdminCallError> {
+    let stream = tokio::net::TcpStream::connect(("localhost", port))
+        .await
+        .context(TcpConnect)?;
+
+    let uri = format!("ws://localhost:{}", port);
+    let mut request = uri.into_client_request().expect("not a valid URI");
+    // needed for admin websocket connection to be accepted
+    request.headers_mut().insert(
+        "origin",
+        "trycp-admin".parse().expect("invalid origin header value"),
+    );
+    request.body();
+
+    let (ws_stream, _) = tokio_tungstenite::client_async_with_config(
+        request,
+        stream,
+        Some(WebSocketConfig::default()),
+    )
+    .await
+    .context(WsConnect)?;
+
+    Ok(ws_stream)
 }
 
 #[derive(Debug, Snafu)]
 pub(crate) enum CallError {
     #[snafu(display("Could not send request over websocket: {}", source))]
     SendRequest { source: tungstenite::Error },
-    #[snafu(display("Did not receive response over websocket"))]
+    #[snafu(display("Admin interface closed before responding"))]
     NoResponse,
-    #[snafu(display("Could not receive response over websocket: {}", source))]
-    ReceiveResponse { source: tungstenite::Error },
-    #[snafu(display("Expected a binary response, got {:?}", response))]
-    UnexpectedResponseType { response: Message },
     #[snafu(display("Timeout while making call"))]
     ResponseTimeout { source: Elapsed },
-    #[snafu(display(
-        "Could not deserialize response {:?} as MessagePack: {}",
-        response,
-        source
-    ))]
-    DeserializeResponse {
-        response: Vec<u8>,
-        source: rmp_serde::decode::Error,
-    },
-    #[snafu(display("Expected a message of type 'Response', got {:?}", message))]
-    UnexpectedMessageType { message: HolochainMessage },
 }
 
 async fn call(
-    ws_stream: &mut WsClientDuplex,
-    request_id: usize,
+    connection: &AdminConnection,
     data: Vec<u8>,
+    timeout: Duration,
 ) -> Result<Vec<u8>, CallError> {
+    let (response_tx, response_rx) = oneshot::channel();
+    let request_id = connection.next_request_id.fetch_add(1, Ordering::Relaxed);
+    connection
+        .pending_requests
+        .lock()
+        .insert(request_id, response_tx);
+
     let request_data = rmp_serde::to_vec_named(&HolochainMessage::Request {
         id: request_id,
         data,
     })
     .unwrap();
 
-    ws_stream
-        .send(Message::Binary(request_data))
-        .await
-        .context(SendRequest)?;
-
-    let ws_data = tokio::time::timeout(std::time::Duration::from_secs(30), async move {
-        loop {
-            let ws_message = ws_stream
-                .next()
-                .await
-                .context(NoResponse)?
-                .context(ReceiveResponse)?;
-            match ws_message {
-                Message::Close(_) => return Err(CallError::NoResponse),
-                Message::Binary(ws_data) => {
-                    return Ok(ws_data);
-                }
+    let result = async {
+        connection
+            .request_writer
+            .lock()
+            .await
+            .send(Message::Binary(request_data))
+            .await
+            .context(SendRequest)?;
+
+        tokio::time::timeout(timeout, response_rx)
+            .await
+            .context(ResponseTimeout)?
+            .map_err(|_| CallError::NoResponse)
+    }
+    .await;
+
+    if result.is_err() {
+        connection.pending_requests.lock().remove(&request_id);
+    }
+
+    result
+}
+
+#[derive(Debug, Snafu)]
+pub(crate) enum ListenError {
+    #[snafu(display("Could not read from websocket: {}", source))]
+    Read { source: tungstenite::Error },
+    #[snafu(display("Expected a binary message, got: {:?}", message))]
+    UnexpectedMessageType { message: Message },
+    #[snafu(display("Could not deserialize bytes {:?} as MessagePack: {}", bytes, source))]
+    DeserializeMessage {
+        bytes: Vec<u8>,
+        source: rmp_serde::decode::Error,
+    },
+    #[snafu(display("Could not send pong: {}", source))]
+    SendPong { source: tungstenite::Error },
+}
+
+/// Route responses from a conductor's admin interface to the pending calls and forward signals to
+/// all clients. When the connection closes, the pending calls are dropped, which fails them.
+async fn listen(
+    id: String,
+    reader: WsReader,
+    request_writer: Arc<futures::lock::Mutex<WsRequestWriter>>,
+    pending_requests: PendingRequests,
+) {
+    let result = reader
+        .map_err(|e| Read.into_error(e))
+        .try_for_each(|message| async {
+            let bytes = match message {
+                Message::Binary(bytes) => bytes,
                 Message::Ping(p) => {
-                    ws_stream
+                    request_writer
+                        .lock()
+                        .await
                         .send(Message::Pong(p))
                         .await
-                        .context(SendRequest)?;
+                        .context(SendPong)?;
+                    return Ok(());
                 }
-                _ => {
-                    return Err(CallError::UnexpectedResponseType {
-                        response: ws_message,
-                    });
+                Message::Pong(_) => return Ok(()),
+                Message::Close(_) => return Ok(()),
+                message => return UnexpectedMessageType { message }.fail(),
+            };
+
+            match rmp_serde::from_slice(&bytes).context(DeserializeMessage { bytes })? {
+                HolochainMessage::Response { id: request_id, data } => {
+                    let pending_request = pending_requests.lock().remove(&request_id);
+                    if let Some(response_tx) = pending_request {
+                        let _ = response_tx.send(data);
+                    } else {
+                        println!(
+                            "warn: received admin response with ID {} without a pending request of that ID",
+                            request_id
+                        );
+                    }
+                }
+                HolochainMessage::Signal { data } => {
+                    broadcast_player_event(&id, "admin_signals", |id| MessageToClient::AdminSignal {
+                        id,
+                        data: data.clone(),
+                    })
+                    .await;
+                }
+                message @ HolochainMessage::Request { .. } => {
+                    println!("warn: ignoring unexpected admin message {:?}", message)
                 }
             }
-        }
-    })
-    .await
-    .context(ResponseTimeout)??;
-
-    let message: HolochainMessage = rmp_serde::from_slice(&ws_data)
-        .with_context(|| DeserializeResponse { response: ws_data })?;
 
-    let response_data = if let HolochainMessage::Response { id: _, data } = message {
-        data
-    } else {
-        return UnexpectedMessageType { message }.fail();
-    };
+            Ok(())
+        })
+        .await;
+    println!("Admin listener for player {:?} closed: {:?}", id, result);
 
-    Ok(response_data)
+    pending_requests.lock().clear();
 }
 
 #[derive(Debug, Snafu)]
@@ -165,9 +304,10 @@ pub(crate) enum AdminDisconnectError {
 }
 
 pub(crate) async fn disconnect(
-    ws_stream: Arc<Mutex<WebSocketStream<TcpStream>>>,
+    connection: Arc<AdminConnection>,
 ) -> Result<(), AdminDisconnectError> {
-    let _ = ws_stream
+    connection
+        .request_writer
         .lock()
         .await
         .send(Message::Close(Some(CloseFrame {
@@ -1,30 +1,52 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use futures::{future, SinkExt, StreamExt, TryStreamExt};
 use once_cell::sync::Lazy;
-use slab::Slab;
-use snafu::{IntoError, OptionExt, ResultExt, Snafu};
+use snafu::{ensure, IntoError, OptionExt, ResultExt, Snafu};
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use tokio_tungstenite::tungstenite::{self, protocol::CloseFrame};
 use tokio_tungstenite::tungstenite::{protocol::frame::coding::CloseCode, Message};
 
+use trycp_api::{ErrorKind, TryCpError};
+
 use crate::{
-    serialize_resp, HolochainMessage, MessageToClient, WsReader, WsRequestWriter, WsResponseWriter,
+    report, serialize_resp, HolochainMessage, MessageToClient, ReportError, WsReader,
+    WsRequestWriter, WsResponseWriter,
 };
 
 pub(crate) struct Connection {
     request_writer: WsRequestWriter,
+    subscribers: Subscribers,
     listen_task: tokio::task::JoinHandle<Result<Result<(), ListenError>, future::Aborted>>,
     cancel_listen_task: future::AbortHandle,
     pending_requests: PendingRequests,
+    /// The id of the next request sent to Holochain. Ids are never reused, so that a late response
+    /// to a call that timed out can't be delivered to the client of a later call.
+    next_request_id: u64,
 }
 
-pub(crate) static APP_CONNECTIONS: Lazy<
-    futures::lock::Mutex<HashMap<u16, Arc<futures::lock::Mutex<Option<Connection>>>>>,
-> = Lazy::new(Default::default);
+type ConnectionLock = Arc<futures::lock::Mutex<Option<Connection>>>;
+
+pub(crate) static APP_CONNECTIONS: Lazy<futures::lock::Mutex<HashMap<u16, ConnectionLock>>> =
+    Lazy::new(Default::default);
+
+type ResponseWriter = Arc<futures::lock::Mutex<WsResponseWriter>>;
 
-type PendingRequests = Arc<futures::lock::Mutex<Slab<u64>>>;
+/// The client sessions that connected to an app interface, to which its signals are forwarded.
+type Subscribers = Arc<futures::lock::Mutex<Vec<ResponseWriter>>>;
+
+type PendingRequests = Arc<futures::lock::Mutex<HashMap<u64, PendingRequest>>>;
+
+/// A call to the app interface that is waiting for Holochain's response.
+pub(crate) struct PendingRequest {
+    request_id: u64,
+    structured_errors: bool,
+    /// The client session that made the call.
+    response_writer: ResponseWriter,
+    /// Fails the request once its deadline has passed, unless aborted because Holochain responded.
+    timeout_task: tokio::task::AbortHandle,
+}
 
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
@@ -56,16 +78,42 @@ pub(crate) enum ConnectError {
     WsConnect { source: tungstenite::Error },
 }
 
+impl ReportError for ConnectError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::TcpConnect { .. } | Self::WsConnect { .. } => ErrorKind::ConductorConnection,
+            Self::SerializeAuth { .. } => ErrorKind::Internal,
+        }
+    }
+}
+
 pub(crate) async fn connect(
     token: Vec<u8>,
     port: u16,
-    response_writer: Arc<futures::lock::Mutex<WsResponseWriter>>,
+    response_writer: ResponseWriter,
 ) -> Result<(), ConnectError> {
     let connection_lock = Arc::clone(APP_CONNECTIONS.lock().await.entry(port).or_default());
 
     let mut connection = connection_lock.lock().await;
-    if connection.is_some() {
-        return Ok(());
+    if let Some(existing) = &*connection {
+        if !existing.listen_task.is_finished() {
+            // Another client session already connected to this port, so subscribe to its signals.
+            let mut subscribers = existing.subscribers.lock().await;
+            if !subscribers
+                .iter()
+                .any(|subscriber| Arc::ptr_eq(subscriber, &response_writer))
+            {
+                subscribers.push(response_writer);
+            }
+            return Ok(());
+        }
+    }
+    // The app interface closed any previous connection, so replace it with one that is
+    // authenticated with this session's token.
+    if let Some(closed) = connection.take() {
+        if let Err(e) = close(port, closed).await {
+            println!("warn: previous connection to app interface on port {port} failed: {e}");
+        }
     }
 
     let addr = format!("localhost:{port}");
@@ -98,8 +146,14 @@ pub(crate) async fn connect(
     let read: WsReader = read;
 
     let pending_requests = Arc::default();
+    let subscribers = Arc::new(futures::lock::Mutex::new(vec![response_writer]));
 
-    let listen_future = listen(port, read, Arc::clone(&pending_requests), response_writer);
+    let listen_future = listen(
+        port,
+        read,
+        Arc::clone(&pending_requests),
+        Arc::clone(&subscribers),
+    );
     let (abortable_listen_future, abort_handle) = future::abortable(listen_future);
 
     let listen_task = tokio::task::spawn(abortable_listen_future);
@@ -118,12 +172,33 @@ pub(crate) async fn connect(
         listen_task,
         cancel_listen_task: abort_handle,
         pending_requests,
+        next_request_id: 0,
         request_writer,
+        subscribers,
     });
 
     Ok(())
 }
 
+/// Stop forwarding signals to a client session, because its connection closed.
+pub(crate) async fn unsubscribe(response_writer: &ResponseWriter) {
+    let connection_locks = APP_CONNECTIONS
+        .lock()
+        .await
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    for connection_lock in connection_locks {
+        if let Some(connection) = &*connection_lock.lock().await {
+            connection
+                .subscribers
+                .lock()
+                .await
+                .retain(|subscriber| !Arc::ptr_eq(subscriber, response_writer));
+        }
+    }
+}
+
 #[derive(Debug, Snafu)]
 pub(crate) enum ListenError {
     #[snafu(display("Could not read from websocket: {}", source))]
@@ -135,10 +210,6 @@ pub(crate) enum ListenError {
         bytes: Vec<u8>,
         source: rmp_serde::decode::Error,
     },
-    #[snafu(display("Could not send response to client: {}", source))]
-    SendResponse { source: tungstenite::Error },
-    #[snafu(display("Could not send signal to client: {}", source))]
-    SendSignal { source: tungstenite::Error },
     #[snafu(display(
         "Received request from Holochain {:?} but Holochain is not supposed to make requests",
         request
@@ -150,17 +221,15 @@ pub(crate) async fn listen(
     port: u16,
     reader: WsReader,
     pending_requests: PendingRequests,
-    response_writer: Arc<futures::lock::Mutex<WsResponseWriter>>,
+    subscribers: Subscribers,
 ) -> Result<(), ListenError> {
     let result = reader
         .map_err(|e| Read.into_error(e))
         .try_for_each(|holochain_message| async {
             let bytes = match holochain_message {
                 Message::Binary(bytes) => bytes,
-                Message::Ping(p) => {
-                    response_writer.lock().await.send(Message::Pong(p)).await.context(SendResponse)?;
-                    return Ok(());
-                },
+                // Pings are answered by tungstenite.
+                Message::Ping(_) | Message::Pong(_) => return Ok(()),
                 message => return UnexpectedMessageType { message }.fail(),
             };
 
@@ -168,20 +237,33 @@ pub(crate) async fn listen(
 
             match deserialized {
                 HolochainMessage::Response { id, data } => {
-                    let call_request_id = {
-                        let mut pending_requests_guard = pending_requests.lock().await;
-                        if pending_requests_guard.contains(id) {
-                            pending_requests_guard.remove(id)
-                        } else {
+                    let pending_request = pending_requests.lock().await.remove(&id);
+                    let pending_request = match pending_request {
+                        Some(pending_request) => {
+                            pending_request.timeout_task.abort();
+                            pending_request
+                        }
+                        None => {
                             println!("warn: received response with ID {} without a pending request of that ID", id);
                             return Ok(())
                         }
                     };
 
-                    response_writer.lock().await.send(Message::Binary(serialize_resp(call_request_id, Ok::<_, String>(data)))).await.context(SendResponse)?;
+                    respond(pending_request, Ok::<_, TryCpError>(data)).await;
                 },
                 HolochainMessage::Signal { data } => {
-                    response_writer.lock().await.send(Message::Binary(rmp_serde::to_vec_named(&MessageToClient::<()>::Signal{port, data}).unwrap())).await.context(SendSignal)?;
+                    let signal = rmp_serde::to_vec_named(&MessageToClient::<()>::Signal { port, data }).unwrap();
+                    let mut subscribers = subscribers.lock().await;
+                    let mut disconnected = Vec::new();
+                    for (index, subscriber) in subscribers.iter().enumerate() {
+                        if let Err(e) = subscriber.lock().await.send(Message::Binary(signal.clone())).await {
+                            println!("warn: could not send signal to client, unsubscribing it: {}", e);
+                            disconnected.push(index);
+                        }
+                    }
+                    for index in disconnected.into_iter().rev() {
+                        subscribers.remove(index);
+                    }
                 }
                 request @ HolochainMessage::Request { .. } =>  {
                     return UnexpectedRequest { request }.fail()
@@ -192,9 +274,44 @@ pub(crate) async fn listen(
         })
         .await;
     println!("App listener closed: {:?}", result);
+    fail_pending_requests(port, &pending_requests).await;
     Ok(())
 }
 
+/// Send the response to a call to the client session that made it.
+async fn respond<R: serde::Serialize>(
+    pending_request: PendingRequest,
+    response: Result<R, TryCpError>,
+) {
+    let response = serialize_resp(
+        pending_request.request_id,
+        response,
+        pending_request.structured_errors,
+    );
+    if let Err(e) = pending_request
+        .response_writer
+        .lock()
+        .await
+        .send(Message::Binary(response))
+        .await
+    {
+        println!("warn: could not send app call response to client: {}", e);
+    }
+}
+
+/// Tell the clients of all calls still waiting for a response that the app interface closed.
+async fn fail_pending_requests(port: u16, pending_requests: &PendingRequests) {
+    let pending_requests = std::mem::take(&mut *pending_requests.lock().await);
+    for pending_request in pending_requests.into_values() {
+        pending_request.timeout_task.abort();
+        respond(
+            pending_request,
+            Err::<(), _>(report(None)(CallError::ConnectionClosed { port })),
+        )
+        .await;
+    }
+}
+
 #[derive(Debug, Snafu)]
 pub(crate) enum AppDisconnectError {
     #[snafu(display("Couldn't complete closing handshake: {}", source))]
@@ -203,28 +320,64 @@ pub(crate) enum AppDisconnectError {
     Listen { source: ListenError },
 }
 
-pub(crate) async fn disconnect_by_port(port: u16) -> Result<(), AppDisconnectError> {
+impl ReportError for AppDisconnectError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::ConductorConnection
+    }
+}
+
+/// Stop forwarding signals of an app interface to a client session. The connection to the app
+/// interface is closed once no client session is connected to it anymore.
+pub(crate) async fn disconnect_by_port(
+    port: u16,
+    response_writer: &ResponseWriter,
+) -> Result<(), AppDisconnectError> {
     let connection_lock = match APP_CONNECTIONS.lock().await.get(&port) {
         Some(connection_lock) => Arc::clone(connection_lock),
         None => return Ok(()),
     };
 
-    disconnect(connection_lock).await
+    let connection = {
+        let mut connection_guard = connection_lock.lock().await;
+        let Some(connection) = &*connection_guard else {
+            return Ok(());
+        };
+        let mut subscribers = connection.subscribers.lock().await;
+        subscribers.retain(|subscriber| !Arc::ptr_eq(subscriber, response_writer));
+        if !subscribers.is_empty() {
+            return Ok(());
+        }
+        drop(subscribers);
+        connection_guard.take()
+    };
+
+    match connection {
+        Some(connection) => close(port, connection).await,
+        None => Ok(()),
+    }
 }
 
 pub(crate) async fn disconnect(
+    port: u16,
     connection_lock: Arc<futures::lock::Mutex<Option<Connection>>>,
 ) -> Result<(), AppDisconnectError> {
-    let mut connection_guard = connection_lock.lock().await;
+    let connection = connection_lock.lock().await.take();
+    match connection {
+        Some(connection) => close(port, connection).await,
+        None => Ok(()),
+    }
+}
+
+/// Close a connection to an app interface, failing the calls still waiting for a response.
+async fn close(port: u16, connection: Connection) -> Result<(), AppDisconnectError> {
     let Connection {
         mut request_writer,
         cancel_listen_task,
         listen_task,
-        pending_requests: _,
-    } = match connection_guard.take() {
-        Some(connection) => connection,
-        None => return Ok(()),
-    };
+        pending_requests,
+        subscribers: _,
+        next_request_id: _,
+    } = connection;
 
     let close_handshake_result = request_writer
         .send(Message::Close(Some(CloseFrame {
@@ -234,8 +387,10 @@ pub(crate) async fn disconnect(
         .await;
 
     cancel_listen_task.abort();
+    let listen_result = listen_task.await.unwrap();
+    fail_pending_requests(port, &pending_requests).await;
 
-    match listen_task.await.unwrap() {
+    match listen_result {
         Ok(Ok(())) | Err(future::Aborted) => close_handshake_result.context(CloseHandshake),
         Ok(Err(e)) => Err(Listen.into_error(e)),
     }
@@ -247,9 +402,31 @@ pub(crate) enum CallError {
     NotConnected { port: u16 },
     #[snafu(display("Could not send request: {}", source))]
     SendRequest { source: tungstenite::Error },
+    #[snafu(display("No response from app interface on port {} within {:?}", port, timeout))]
+    ResponseTimeout { port: u16, timeout: Duration },
+    #[snafu(display("App interface on port {} closed before responding", port))]
+    ConnectionClosed { port: u16 },
 }
 
-pub(crate) async fn call(request_id: u64, port: u16, message: Vec<u8>) -> Result<(), CallError> {
+impl ReportError for CallError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::NotConnected { .. } => ErrorKind::AppInterfaceNotConnected,
+            Self::SendRequest { .. } => ErrorKind::ConductorConnection,
+            Self::ResponseTimeout { .. } => ErrorKind::Timeout,
+            Self::ConnectionClosed { .. } => ErrorKind::ConductorConnection,
+        }
+    }
+}
+
+pub(crate) async fn call(
+    request_id: u64,
+    structured_errors: bool,
+    port: u16,
+    message: Vec<u8>,
+    timeout: Duration,
+    response_writer: ResponseWriter,
+) -> Result<(), CallError> {
     let connection_lock = Arc::clone(
         APP_CONNECTIONS
             .lock()
@@ -259,8 +436,28 @@ pub(crate) async fn call(request_id: u64, port: u16, message: Vec<u8>) -> Result
     );
     let mut connection_guard = connection_lock.lock().await;
     let connection = connection_guard.as_mut().context(NotConnected { port })?;
+    ensure!(
+        !connection.listen_task.is_finished(),
+        ConnectionClosed { port }
+    );
 
-    let holochain_request_id = connection.pending_requests.lock().await.insert(request_id);
+    let holochain_request_id = connection.next_request_id;
+    connection.next_request_id += 1;
+    let timeout_task = tokio::task::spawn(expire(
+        port,
+        holochain_request_id,
+        timeout,
+        Arc::clone(&connection.pending_requests),
+    ));
+    connection.pending_requests.lock().await.insert(
+        holochain_request_id,
+        PendingRequest {
+            request_id,
+            structured_errors,
+            response_writer,
+            timeout_task: timeout_task.abort_handle(),
+        },
+    );
     if let Err(e) = connection
         .request_writer
         .send(Message::Binary(
@@ -272,9 +469,13 @@ pub(crate) async fn call(request_id: u64, port: u16, message: Vec<u8>) -> Result
         ))
         .await
     {
-        let mut pending_requests = connection.pending_requests.lock().await;
-        if pending_requests.contains(holochain_request_id) {
-            pending_requests.remove(holochain_request_id);
+        let pending_request = connection
+            .pending_requests
+            .lock()
+            .await
+            .remove(&holochain_request_id);
+        if let Some(pending_request) = pending_request {
+            pending_request.timeout_task.abort();
         }
 
         return Err(SendRequest.into_error(e));
@@ -282,3 +483,23 @@ pub(crate) async fn call(request_id: u64, port: u16, message: Vec<u8>) -> Result
 
     Ok(())
 }
+
+/// Wait for a call's deadline, then forget about the call and tell the client that it timed out.
+async fn expire(
+    port: u16,
+    holochain_request_id: u64,
+    timeout: Duration,
+    pending_requests: PendingRequests,
+) {
+    tokio::time::sleep(timeout).await;
+
+    let Some(pending_request) = pending_requests.lock().await.remove(&holochain_request_id) else {
+        return;
+    };
+
+    respond(
+        pending_request,
+        Err::<(), _>(report(None)(CallError::ResponseTimeout { port, timeout })),
+    )
+    .await;
+}
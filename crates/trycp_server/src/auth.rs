@@ -0,0 +1,104 @@
+use std::{collections::HashSet, io, path::PathBuf};
+
+use once_cell::sync::OnceCell;
+use snafu::{ensure, ResultExt, Snafu};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::{header::AUTHORIZATION, StatusCode},
+};
+use trycp_api::ErrorKind;
+
+use crate::ReportError;
+
+/// The tokens that clients may authenticate with, or `None` if no authentication is required.
+static AUTH_TOKENS: OnceCell<Option<HashSet<String>>> = OnceCell::new();
+
+#[derive(Debug, Snafu)]
+pub(crate) enum LoadTokensError {
+    #[snafu(display("Could not read auth token file {}: {}", path.display(), source))]
+    ReadTokenFile { path: PathBuf, source: io::Error },
+    #[snafu(display("Auth token file {} does not contain any tokens", path.display()))]
+    NoTokens { path: PathBuf },
+}
+
+#[derive(Debug, Snafu)]
+pub(crate) enum AuthError {
+    #[snafu(display("Authentication required, send an authenticate request first"))]
+    Unauthenticated,
+    #[snafu(display("Invalid auth token"))]
+    InvalidToken,
+}
+
+impl ReportError for AuthError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Unauthenticated
+    }
+}
+
+/// Load the tokens clients may authenticate with, one per line. Empty lines and lines starting
+/// with `#` are ignored. Without a token file, clients don't need to authenticate.
+pub(crate) fn load_tokens(path: Option<PathBuf>) -> Result<(), LoadTokensError> {
+    let tokens = match path {
+        Some(path) => {
+            let contents = std::fs::read_to_string(&path).context(ReadTokenFile { path: &path })?;
+            let tokens = contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(ToString::to_string)
+                .collect::<HashSet<_>>();
+            ensure!(!tokens.is_empty(), NoTokens { path });
+            Some(tokens)
+        }
+        None => None,
+    };
+    AUTH_TOKENS
+        .set(tokens)
+        .expect("auth tokens should only be set once");
+    Ok(())
+}
+
+pub(crate) fn auth_required() -> bool {
+    AUTH_TOKENS.get().is_some_and(Option::is_some)
+}
+
+pub(crate) fn check_token(token: &str) -> Result<(), AuthError> {
+    if let Some(Some(tokens)) = AUTH_TOKENS.get() {
+        ensure!(tokens.contains(token), InvalidToken);
+    }
+    Ok(())
+}
+
+/// Check the `Authorization: Bearer <token>` header of a websocket upgrade request, if there is
+/// one. Upgrades with an invalid token are rejected, while upgrades without one are accepted so
+/// that the client can authenticate with a request instead. Records whether the client is
+/// authenticated.
+// The error type is dictated by tungstenite's handshake callback.
+#[allow(clippy::result_large_err)]
+pub(crate) fn check_upgrade<'a>(
+    authenticated: &'a mut bool,
+) -> impl FnOnce(&Request, Response) -> Result<Response, ErrorResponse> + 'a {
+    move |request, response| {
+        let token = match request.headers().get(AUTHORIZATION) {
+            Some(header) => header
+                .to_str()
+                .ok()
+                .and_then(|header| header.strip_prefix("Bearer ")),
+            None => {
+                *authenticated = !auth_required();
+                return Ok(response);
+            }
+        };
+        match token.map(check_token) {
+            Some(Ok(())) => {
+                *authenticated = true;
+                Ok(response)
+            }
+            _ => {
+                let mut error = ErrorResponse::new(Some(AuthError::InvalidToken.to_string()));
+                *error.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(error)
+            }
+        }
+    }
+}
@@ -1,12 +1,13 @@
 use std::{io, net::TcpListener, path::PathBuf, sync::atomic::Ordering};
 
 use parking_lot::Mutex;
-use snafu::{ensure, ResultExt, Snafu};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
 use std::str;
+use trycp_api::ErrorKind;
 
 use crate::{
-    get_player_dir, player_config_exists, Player, ADMIN_PORT_RANGE, CONDUCTOR_CONFIG_FILENAME,
-    NEXT_ADMIN_PORT, PLAYERS,
+    admin_port_range, get_player_dir, holochain_bin, player_config_exists, Player, ReportError,
+    CONDUCTOR_CONFIG_FILENAME, FREE_ADMIN_PORTS, NEXT_ADMIN_PORT, PLAYERS,
 };
 
 #[derive(Debug, Snafu)]
@@ -19,12 +20,32 @@ pub(crate) enum ConfigurePlayerError {
     OutOfPorts,
     #[snafu(display("Player with ID {} has already been configured", id))]
     PlayerAlreadyConfigured { id: String },
+    #[snafu(display("No holochain binary is registered for version {:?}", version))]
+    UnknownHolochainVersion { version: String },
+}
+
+impl ReportError for ConfigurePlayerError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::CreateDir { .. } | Self::CreateConfig { .. } => ErrorKind::Io,
+            Self::OutOfPorts => ErrorKind::OutOfPorts,
+            Self::PlayerAlreadyConfigured { .. } => ErrorKind::PlayerAlreadyConfigured,
+            Self::UnknownHolochainVersion { .. } => ErrorKind::UnknownHolochainVersion,
+        }
+    }
 }
 
 pub(crate) fn configure_player(
     id: String,
     partial_config: String,
+    holochain_version: Option<String>,
+    session: u64,
 ) -> Result<(), ConfigurePlayerError> {
+    let holochain_bin =
+        holochain_bin(holochain_version.as_deref()).with_context(|| UnknownHolochainVersion {
+            version: holochain_version.clone().unwrap_or_default(),
+        })?;
+
     let player_dir = get_player_dir(&id);
     let config_path = player_dir.join(CONDUCTOR_CONFIG_FILENAME);
 
@@ -34,15 +55,16 @@ pub(crate) fn configure_player(
 
     ensure!(!player_config_exists(&id), PlayerAlreadyConfigured { id });
 
-    let mut admin_port = NEXT_ADMIN_PORT.fetch_add(1, Ordering::SeqCst);
+    let admin_port_range = admin_port_range();
+    let mut admin_port = next_admin_port();
     loop {
-        ensure!(ADMIN_PORT_RANGE.contains(&admin_port), OutOfPorts);
+        ensure!(admin_port_range.contains(&admin_port), OutOfPorts);
         let listener = TcpListener::bind(format!("localhost:{admin_port}"));
         if let Ok(p) = listener {
             admin_port = p.local_addr().unwrap().port();
             break;
         }
-        admin_port = NEXT_ADMIN_PORT.fetch_add(1, Ordering::SeqCst);
+        admin_port = next_admin_port();
     }
     println!("Admin port {admin_port} assigned to player {id}.");
 
@@ -55,7 +77,11 @@ pub(crate) fn configure_player(
             id.clone(),
             Player {
                 admin_port,
+                session,
+                holochain_bin,
                 processes: Mutex::default(),
+                app_ports: Mutex::default(),
+                last_exit_status: Mutex::default(),
             },
         );
     }
@@ -86,3 +112,11 @@ admin_interfaces:
     );
     Ok(())
 }
+
+/// Take the lowest admin port freed by a removed player, or else the next unused one.
+fn next_admin_port() -> u16 {
+    FREE_ADMIN_PORTS
+        .lock()
+        .pop_first()
+        .unwrap_or_else(|| NEXT_ADMIN_PORT.fetch_add(1, Ordering::SeqCst))
+}
@@ -6,7 +6,9 @@ use std::{
 use snafu::{IntoError, ResultExt, Snafu};
 use tokio::io::AsyncWriteExt;
 
-use crate::DNA_DIR_PATH;
+use trycp_api::ErrorKind;
+
+use crate::{dna_dir, ReportError};
 #[derive(Debug, Snafu)]
 pub(crate) enum DownloadDnaError {
     #[snafu(display("Could not parse URL {:?}: {}", url, source))]
@@ -26,6 +28,18 @@ pub(crate) enum DownloadDnaError {
     ParseResponse { url: String, source: reqwest::Error },
 }
 
+impl ReportError for DownloadDnaError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ParseUrl { .. } => ErrorKind::InvalidRequest,
+            Self::CreateDna { .. } | Self::OpenSource { .. } | Self::WriteDna { .. } => {
+                ErrorKind::Io
+            }
+            Self::RequestDna { .. } | Self::ParseResponse { .. } => ErrorKind::Download,
+        }
+    }
+}
+
 pub(crate) async fn download_dna(url_str: String) -> Result<String, DownloadDnaError> {
     let url = url::Url::parse(&url_str).with_context(|| ParseUrl {
         url: url_str.clone(),
@@ -72,7 +86,7 @@ pub(crate) async fn download_dna(url_str: String) -> Result<String, DownloadDnaE
 }
 
 fn get_downloaded_dna_path(url: &url::Url) -> PathBuf {
-    Path::new(DNA_DIR_PATH)
+    dna_dir()
         .join(url.scheme())
         .join(url.path().replace('/', "").replace('%', "_"))
 }
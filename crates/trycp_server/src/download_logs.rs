@@ -1,9 +1,9 @@
 use crate::{
-    get_player_dir, player_config_exists, CONDUCTOR_STDERR_LOG_FILENAME,
+    get_player_dir, player_config_exists, ReportError, CONDUCTOR_STDERR_LOG_FILENAME,
     CONDUCTOR_STDOUT_LOG_FILENAME,
 };
 use snafu::{ResultExt, Snafu};
-use trycp_api::{DownloadLogsResponse, MessageResponse, TryCpServerResponse};
+use trycp_api::{DownloadLogsResponse, ErrorKind, MessageResponse, TryCpServerResponse};
 
 #[derive(Debug, Snafu)]
 pub(crate) enum DownloadLogsError {
@@ -25,6 +25,16 @@ pub(crate) enum DownloadLogsError {
     SerializeResponse { source: rmp_serde::encode::Error },
 }
 
+impl ReportError for DownloadLogsError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::PlayerNotConfigured { .. } => ErrorKind::PlayerNotConfigured,
+            Self::HolochainStdout { .. } | Self::HolochainStderr { .. } => ErrorKind::Io,
+            Self::SerializeResponse { .. } => ErrorKind::Internal,
+        }
+    }
+}
+
 pub(crate) fn download_logs(id: String) -> Result<MessageResponse, DownloadLogsError> {
     if !player_config_exists(&id) {
         return Err(DownloadLogsError::PlayerNotConfigured { id });
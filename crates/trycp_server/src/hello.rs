@@ -0,0 +1,106 @@
+use std::{
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::OnceCell;
+use snafu::{ResultExt, Snafu};
+use trycp_api::{
+    ErrorKind, HelloResponse, MessageResponse, TryCpServerResponse, FEATURES, PROTOCOL_VERSION,
+    REQUEST_TYPES,
+};
+
+use crate::{holochain_bin, ReportError};
+
+/// How long `holochain --version` may take before the version is reported as unknown.
+const HOLOCHAIN_VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+static HOLOCHAIN_VERSION: OnceCell<Option<String>> = OnceCell::new();
+
+#[derive(Debug, Snafu)]
+pub(crate) enum HelloError {
+    #[snafu(display(
+        "Client speaks TryCP protocol version {} but this server speaks version {}",
+        client_version,
+        PROTOCOL_VERSION
+    ))]
+    ProtocolMismatch { client_version: u32 },
+    #[snafu(display("Could not serialize response: {}", source))]
+    SerializeResponse { source: rmp_serde::encode::Error },
+}
+
+impl ReportError for HelloError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ProtocolMismatch { .. } => ErrorKind::ProtocolMismatch,
+            Self::SerializeResponse { .. } => ErrorKind::Internal,
+        }
+    }
+}
+
+pub(crate) fn hello(
+    client_version: u32,
+    features: Vec<String>,
+) -> Result<MessageResponse, HelloError> {
+    if client_version != PROTOCOL_VERSION {
+        return ProtocolMismatch { client_version }.fail();
+    }
+
+    let response = HelloResponse {
+        protocol_version: PROTOCOL_VERSION,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        request_types: REQUEST_TYPES.iter().map(ToString::to_string).collect(),
+        features: features
+            .into_iter()
+            .filter(|feature| FEATURES.contains(&feature.as_str()))
+            .collect(),
+        holochain_version: HOLOCHAIN_VERSION
+            .get_or_init(|| holochain_version(&holochain_bin(None)?))
+            .clone(),
+    };
+
+    Ok(MessageResponse::Bytes(
+        rmp_serde::to_vec_named(&TryCpServerResponse::Hello(response))
+            .context(SerializeResponse)?,
+    ))
+}
+
+/// Ask a holochain binary for its version, giving up if it doesn't answer in time.
+fn holochain_version(holochain_bin: &Path) -> Option<String> {
+    let mut holochain = Command::new(holochain_bin)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let deadline = Instant::now() + HOLOCHAIN_VERSION_TIMEOUT;
+    let status = loop {
+        match holochain.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            _ => {
+                println!("could not determine holochain version of {holochain_bin:?}");
+                let _ = holochain.kill();
+                let _ = holochain.wait();
+                return None;
+            }
+        }
+    };
+    if !status.success() {
+        return None;
+    }
+
+    let mut output = String::new();
+    holochain.stdout.take()?.read_to_string(&mut output).ok()?;
+    let output = output.trim();
+    Some(
+        output
+            .strip_prefix("holochain ")
+            .unwrap_or(output)
+            .to_string(),
+    )
+}
@@ -3,35 +3,52 @@
 
 mod admin_call;
 mod app_interface;
+mod auth;
 mod configure_player;
 mod download_dna;
 mod download_logs;
+mod hello;
+mod pause;
+mod player_status;
+mod remove_player;
 mod reset;
+mod restart;
 mod save_dna;
+mod session;
 mod shutdown;
 mod startup;
+mod tls;
+mod watch_conductor;
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, HashSet},
     fmt::Debug,
     io,
+    net::IpAddr,
+    ops::Range,
     path::{Path, PathBuf},
+    pin::Pin,
     process::Child,
-    sync::{atomic::AtomicU16, Arc},
+    sync::{
+        atomic::{self, AtomicU16},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
-use futures::{stream::SplitStream, SinkExt, StreamExt};
+use futures::{stream::SplitStream, Sink, SinkExt, StreamExt};
 use nix::{
     sys::signal::{self, Signal},
     unistd::Pid,
 };
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
+use slab::Slab;
 use snafu::ResultExt;
 use snafu::Snafu;
 use structopt::StructOpt;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::task::spawn_blocking;
 use tokio_tungstenite::{
     tungstenite::{self, Message},
@@ -46,12 +63,26 @@ const CONDUCTOR_CONFIG_FILENAME: &str = "conductor-config.yml";
 const LAIR_PASSPHRASE: &str = "passphrase";
 const CONDUCTOR_STDOUT_LOG_FILENAME: &str = "conductor-stdout.txt";
 const CONDUCTOR_STDERR_LOG_FILENAME: &str = "conductor-stderr.txt";
-const PLAYERS_DIR_PATH: &str = "/tmp/trycp/players";
-const DNA_DIR_PATH: &str = "/tmp/trycp/dnas";
-const ADMIN_PORT_RANGE: std::ops::Range<u16> = 9100..9200;
-
-static NEXT_ADMIN_PORT: AtomicU16 = AtomicU16::new(ADMIN_PORT_RANGE.start);
+const DEFAULT_HOLOCHAIN_BIN: &str = "holochain";
+const DEFAULT_DATA_DIR_PATH: &str = "/tmp/trycp";
+const PLAYERS_DIR_NAME: &str = "players";
+const DNA_DIR_NAME: &str = "dnas";
+const REMOVED_PLAYERS_DIR_NAME: &str = "removed-players";
+/// The directory within the players directory that holds a subdirectory for each namespace.
+const NAMESPACES_DIR_NAME: &str = ".ns";
+const DEFAULT_ADMIN_PORT_RANGE: Range<u16> = 9100..9200;
+
+static NEXT_ADMIN_PORT: AtomicU16 = AtomicU16::new(DEFAULT_ADMIN_PORT_RANGE.start);
+static ADMIN_PORT_RANGE: OnceCell<Range<u16>> = OnceCell::new();
+/// Admin ports of removed players, which are handed out again before new ones.
+static FREE_ADMIN_PORTS: Lazy<Mutex<BTreeSet<u16>>> = Lazy::new(Mutex::default);
+static HOLOCHAIN_BIN: OnceCell<PathBuf> = OnceCell::new();
+static HOLOCHAIN_VERSIONS: OnceCell<HashMap<String, PathBuf>> = OnceCell::new();
 static PLAYERS: Lazy<RwLock<HashMap<String, Player>>> = Lazy::new(RwLock::default);
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+static CALL_TIMEOUT: OnceCell<Duration> = OnceCell::new();
+/// The authenticated sessions, which receive notifications.
+static CLIENTS: Lazy<Mutex<Slab<Arc<session::Session>>>> = Lazy::new(Mutex::default);
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -64,23 +95,140 @@ async fn main() -> Result<(), Error> {
             default_value = "9000"
         )]
         port: u16,
+        #[structopt(
+            long = "bind",
+            help = "The address to bind the trycp server to",
+            default_value = "0.0.0.0"
+        )]
+        bind: IpAddr,
+        #[structopt(
+            long = "admin-port-range",
+            help = "The range of ports, given as START..END, from which conductor admin ports are assigned",
+            default_value = "9100..9200",
+            parse(try_from_str = "parse_port_range")
+        )]
+        admin_port_range: Range<u16>,
+        #[structopt(
+            long = "data-dir",
+            help = "The directory in which player and DNA files are stored",
+            default_value = "/tmp/trycp",
+            env = "TRYCP_DATA_DIR",
+            parse(from_os_str)
+        )]
+        data_dir: PathBuf,
+        #[structopt(
+            long = "holochain-bin",
+            help = "The holochain binary to run conductors with",
+            default_value = "holochain",
+            parse(from_os_str)
+        )]
+        holochain_bin: PathBuf,
+        #[structopt(
+            long = "holochain-version",
+            help = "A named holochain binary, given as NAME=PATH, that players can be configured to run with; can be given multiple times",
+            parse(try_from_str = "parse_holochain_version")
+        )]
+        holochain_versions: Vec<(String, PathBuf)>,
+        #[structopt(
+            long = "call-timeout-ms",
+            help = "How long to wait for a conductor's response to an admin or app call that doesn't set its own timeout, in milliseconds",
+            default_value = "30000"
+        )]
+        call_timeout_ms: u64,
+        #[structopt(
+            long = "on-disconnect",
+            help = "What to do with the players a client configured when its connection ends: keep them, shutdown their conductors, or remove them with reset-session",
+            default_value = "keep",
+            raw(possible_values = r#"&["keep", "shutdown", "reset-session"]"#)
+        )]
+        on_disconnect: session::OnDisconnect,
+        #[structopt(
+            long = "auth-token-file",
+            help = "A file with the tokens, one per line, that clients must authenticate with; if not given, clients don't need to authenticate",
+            parse(from_os_str)
+        )]
+        auth_token_file: Option<PathBuf>,
+        #[structopt(
+            long = "tls-cert",
+            help = "A PEM file with the certificate chain to serve wss:// connections with; requires --tls-key",
+            parse(from_os_str)
+        )]
+        tls_cert: Option<PathBuf>,
+        #[structopt(
+            long = "tls-key",
+            help = "A PEM file with the private key of the --tls-cert certificate",
+            parse(from_os_str)
+        )]
+        tls_key: Option<PathBuf>,
+        #[structopt(
+            long = "keep-data",
+            help = "Do not remove existing players and DNAs from the data directory on startup"
+        )]
+        keep_data: bool,
     }
     let args = Cli::from_args();
 
-    let _ = tokio::fs::remove_dir_all("/tmp/trycp").await;
+    DATA_DIR
+        .set(args.data_dir)
+        .expect("data directory should only be set once");
+    NEXT_ADMIN_PORT.store(args.admin_port_range.start, atomic::Ordering::SeqCst);
+    ADMIN_PORT_RANGE
+        .set(args.admin_port_range)
+        .expect("admin port range should only be set once");
+    HOLOCHAIN_BIN
+        .set(args.holochain_bin)
+        .expect("holochain binary should only be set once");
+    HOLOCHAIN_VERSIONS
+        .set(args.holochain_versions.into_iter().collect())
+        .expect("holochain versions should only be set once");
+    auth::load_tokens(args.auth_token_file).context(LoadAuthTokens)?;
+    let tls_acceptor = match (args.tls_cert, args.tls_key) {
+        (Some(cert), Some(key)) => Some(tls::load_acceptor(cert, key).context(LoadTls)?),
+        (None, None) => None,
+        _ => return TlsCertWithoutKey.fail(),
+    };
+    session::ON_DISCONNECT
+        .set(args.on_disconnect)
+        .expect("on disconnect policy should only be set once");
+    CALL_TIMEOUT
+        .set(Duration::from_millis(args.call_timeout_ms))
+        .expect("call timeout should only be set once");
+
+    // Only remove the directories the server creates, as the data directory may be one that is
+    // also used for other files.
+    if !args.keep_data {
+        for dir in [players_dir(), dna_dir(), removed_players_dir()] {
+            let _ = tokio::fs::remove_dir_all(dir).await;
+        }
+    }
 
-    let addr = format!("0.0.0.0:{}", args.port);
+    let addr = std::net::SocketAddr::new(args.bind, args.port);
 
     let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .context(BindServer)?;
 
-    println!("Listening on {}", addr);
+    println!(
+        "Listening on {}{}",
+        addr,
+        if tls_acceptor.is_some() {
+            " with TLS"
+        } else {
+            ""
+        }
+    );
     let mut client_futures = vec![];
     while let Ok((stream, addr)) = listener.accept().await {
+        let tls_acceptor = tls_acceptor.clone();
         client_futures.push(tokio::spawn(async move {
-            ws_connection(stream)
-                .await
+            let result = match tls_acceptor {
+                Some(tls_acceptor) => match tls_acceptor.accept(stream).await {
+                    Ok(stream) => ws_connection(stream).await,
+                    Err(source) => Err(ConnectionError::TlsHandshake { source }),
+                },
+                None => ws_connection(stream).await,
+            };
+            result
                 .unwrap_or_else(|e| println!("Error serving client from address ({}): {}", addr, e))
         }));
     }
@@ -90,38 +238,185 @@ async fn main() -> Result<(), Error> {
     Ok(())
 }
 
+fn parse_port_range(range: &str) -> Result<Range<u16>, String> {
+    let (start, end) = range
+        .split_once("..")
+        .ok_or_else(|| format!("expected a port range of the form START..END, got {range:?}"))?;
+    let start = start
+        .parse::<u16>()
+        .map_err(|e| format!("invalid start port {start:?}: {e}"))?;
+    let end = end
+        .parse::<u16>()
+        .map_err(|e| format!("invalid end port {end:?}: {e}"))?;
+    if start >= end {
+        return Err(format!("port range {range:?} is empty"));
+    }
+    Ok(start..end)
+}
+
+fn parse_holochain_version(version: &str) -> Result<(String, PathBuf), String> {
+    let (name, path) = version.split_once('=').ok_or_else(|| {
+        format!("expected a holochain version of the form NAME=PATH, got {version:?}")
+    })?;
+    if name.is_empty() || path.is_empty() {
+        return Err(format!(
+            "expected a holochain version of the form NAME=PATH, got {version:?}"
+        ));
+    }
+    Ok((name.to_string(), PathBuf::from(path)))
+}
+
+/// The time to wait for a conductor's response to an admin or app call, either as requested or the
+/// server's default.
+fn call_timeout(timeout_ms: Option<u64>) -> Duration {
+    timeout_ms.map(Duration::from_millis).unwrap_or_else(|| {
+        *CALL_TIMEOUT
+            .get()
+            .expect("call timeout should have been set")
+    })
+}
+
+/// Resolve the holochain binary to run a conductor with, either the named version or the
+/// server's default binary.
+fn holochain_bin(version: Option<&str>) -> Option<PathBuf> {
+    match version {
+        Some(version) => HOLOCHAIN_VERSIONS.get()?.get(version).cloned(),
+        None => Some(
+            HOLOCHAIN_BIN
+                .get()
+                .cloned()
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_HOLOCHAIN_BIN)),
+        ),
+    }
+}
+
+fn admin_port_range() -> Range<u16> {
+    ADMIN_PORT_RANGE
+        .get()
+        .cloned()
+        .unwrap_or(DEFAULT_ADMIN_PORT_RANGE)
+}
+
 #[derive(Debug, Snafu)]
 enum Error {
     #[snafu(display("Could not bind websocket server: {}", source))]
     BindServer { source: io::Error },
+    #[snafu(display("{}", source))]
+    LoadAuthTokens { source: auth::LoadTokensError },
+    #[snafu(display("{}", source))]
+    LoadTls { source: tls::LoadTlsError },
+    #[snafu(display("--tls-cert and --tls-key must be given together"))]
+    TlsCertWithoutKey,
 }
 
 #[derive(Default)]
 struct Player {
     admin_port: u16,
+    /// The client session that configured this player.
+    session: u64,
+    holochain_bin: PathBuf,
     processes: Mutex<Option<PlayerProcesses>>,
+    /// App interface ports attached to this player's conductor through the admin interface.
+    app_ports: Mutex<HashSet<u16>>,
+    last_exit_status: Mutex<Option<ConductorExitStatus>>,
 }
 
 struct PlayerProcesses {
     holochain: Child,
+    started_at: Instant,
+    paused: bool,
+}
+
+/// Serialize a response to a request. Unless the client asked for structured errors, errors are
+/// sent as plain messages.
+fn serialize_resp<R: Serialize>(
+    id: u64,
+    response: Result<R, TryCpError>,
+    structured_errors: bool,
+) -> Vec<u8> {
+    if structured_errors {
+        rmp_serde::to_vec_named(&MessageToClient::Response { response, id })
+    } else {
+        rmp_serde::to_vec_named(&MessageToClient::Response {
+            response: response.map_err(|e| e.message),
+            id,
+        })
+    }
+    .unwrap()
+}
+
+/// Errors which are reported to clients as a [TryCpError].
+trait ReportError: std::error::Error {
+    /// The kind of error to report.
+    fn kind(&self) -> ErrorKind;
 }
 
-fn serialize_resp<R: Serialize>(id: u64, data: R) -> Vec<u8> {
-    rmp_serde::to_vec_named(&MessageToClient::Response { response: data, id }).unwrap()
+/// Make a function that converts an error into a [TryCpError] about the given player.
+fn report<E: ReportError>(player_id: Option<&str>) -> impl FnOnce(E) -> TryCpError {
+    let player_id = player_id.map(str::to_string);
+    move |e| TryCpError {
+        kind: e.kind(),
+        message: e.to_string(),
+        player_id,
+        details: std::error::Error::source(&e).map(ToString::to_string),
+    }
+}
+
+/// Notify the clients of a player's namespace that negotiated the given feature about an event of
+/// the player. The message is built with the player's id as the client knows it.
+async fn broadcast_player_event(
+    key: &str,
+    feature: &str,
+    message: impl Fn(String) -> MessageToClient<()>,
+) {
+    let clients = CLIENTS
+        .lock()
+        .iter()
+        .filter(|(_, session)| session.has_feature(feature))
+        .filter_map(|(_, session)| Some((Arc::clone(session), session.notification_id(key)?)))
+        .collect::<Vec<_>>();
+    for (session, id) in clients {
+        let message = rmp_serde::to_vec_named(&message(id)).unwrap();
+        if let Err(e) = session
+            .response_writer
+            .lock()
+            .await
+            .send(Message::Binary(message))
+            .await
+        {
+            println!("warn: could not send message to client: {}", e);
+        }
+    }
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
 enum MessageToClient<R> {
-    Signal { port: u16, data: Vec<u8> },
-    Response { id: u64, response: R },
+    Signal {
+        port: u16,
+        data: Vec<u8>,
+    },
+    Response {
+        id: u64,
+        response: R,
+    },
+    ConductorExited {
+        id: String,
+        status: ConductorExitStatus,
+    },
+    AdminSignal {
+        id: String,
+        data: Vec<u8>,
+    },
 }
 
 #[derive(Debug, Snafu)]
 enum ConnectionError {
     #[snafu(display("Could not complete handshake with client: {}", source))]
     Handshake { source: tungstenite::Error },
+    #[snafu(display("Could not complete TLS handshake with client: {}", source))]
+    TlsHandshake { source: io::Error },
     #[snafu(display("Could not read request from websocket: {}", source))]
     ReadRequest { source: tungstenite::Error },
     #[snafu(display("Could not write response to websocket: {}", source))]
@@ -139,15 +434,24 @@ enum ConnectionError {
     },
 }
 
-async fn ws_connection(stream: TcpStream) -> Result<(), ConnectionError> {
-    let ws_stream = tokio_tungstenite::accept_async(stream)
-        .await
-        .context(Handshake)?;
+async fn ws_connection<S>(stream: S) -> Result<(), ConnectionError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut authenticated = false;
+    let ws_stream =
+        tokio_tungstenite::accept_hdr_async(stream, auth::check_upgrade(&mut authenticated))
+            .await
+            .context(Handshake)?;
 
     let (ws_write, ws_read) = ws_stream.split();
 
-    let ws_write1 = Arc::new(futures::lock::Mutex::new(ws_write));
-    let ws_write2 = &ws_write1;
+    let ws_write1: Arc<futures::lock::Mutex<WsResponseWriter>> =
+        Arc::new(futures::lock::Mutex::new(Box::pin(ws_write)));
+    let session = Arc::new(session::Session::new(Arc::clone(&ws_write1)));
+    if authenticated {
+        session.authenticate();
+    }
 
     let write = futures::sink::unfold((), |(), response| async {
         if let Some(response) = response {
@@ -158,16 +462,21 @@ async fn ws_connection(stream: TcpStream) -> Result<(), ConnectionError> {
         }
     });
 
-    ws_read
-        .then(|message_res| ws_message(message_res, Arc::clone(ws_write2)))
+    let result = ws_read
+        .then(|message_res| ws_message(message_res, Arc::clone(&session)))
         .forward(write)
-        .await
+        .await;
+
+    session::end_session(&session).await;
+
+    result
 }
 
 async fn ws_message(
     message_res: Result<Message, tungstenite::Error>,
-    ws_write: Arc<futures::lock::Mutex<WsResponseWriter>>,
+    session: Arc<session::Session>,
 ) -> Result<Option<Message>, ConnectionError> {
+    let ws_write = Arc::clone(&session.response_writer);
     let message = message_res.context(ReadRequest)?;
 
     let bytes = match message {
@@ -183,75 +492,277 @@ async fn ws_message(
         _ => return UnexpectedMessageType { message }.fail(),
     };
 
+    // Clients that negotiated structured errors with a hello request receive them for every
+    // request.
+    let negotiated_structured_errors = session.has_feature("structured_errors");
+
     let RequestWrapper {
         id: request_id,
         request,
-    } = rmp_serde::from_slice(&bytes).context(DeserializeRequestWrapper { bytes })?;
+        structured_errors,
+        namespace,
+    } = match rmp_serde::from_slice(&bytes) {
+        Ok(request) => request,
+        Err(source) => match unknown_request(&bytes, &source, negotiated_structured_errors) {
+            Some(response) => return Ok(Some(Message::Binary(response))),
+            None => return Err(source).context(DeserializeRequestWrapper { bytes }),
+        },
+    };
+    let structured_errors = structured_errors || negotiated_structured_errors;
+
+    let authentication_needed = !matches!(
+        request,
+        Request::Hello { .. } | Request::Authenticate { .. }
+    );
+    if authentication_needed && !session.is_authenticated() {
+        let response = serialize_resp(
+            request_id,
+            Err::<(), _>(report(None)(auth::AuthError::Unauthenticated)),
+            structured_errors,
+        );
+        return Ok(Some(Message::Binary(response)));
+    }
+
+    if let Some(namespace) = &namespace {
+        if let Err(e) = validate_namespace(namespace) {
+            let response =
+                serialize_resp(request_id, Err::<(), _>(report(None)(e)), structured_errors);
+            return Ok(Some(Message::Binary(response)));
+        }
+        session.enter_namespace(namespace);
+    }
+    if let Some(id) = request_player_id(&request) {
+        if let Err(e) = validate_player_id(id) {
+            let response = serialize_resp(
+                request_id,
+                Err::<(), _>(report(Some(id))(e)),
+                structured_errors,
+            );
+            return Ok(Some(Message::Binary(response)));
+        }
+    }
 
     let response = match request {
+        Request::Hello {
+            client_version,
+            features,
+        } => {
+            let resp = spawn_blocking(move || {
+                hello::hello(client_version, features.clone()).map(|response| (response, features))
+            })
+            .await
+            .unwrap()
+            .map(|(response, features)| {
+                session.set_features(features);
+                response
+            });
+            serialize_resp(request_id, resp.map_err(report(None)), structured_errors)
+        }
+        Request::Authenticate { token } => {
+            let resp = auth::check_token(&token).map_err(report(None));
+            if resp.is_ok() {
+                session.authenticate();
+            }
+            serialize_resp(request_id, resp, structured_errors)
+        }
         Request::SaveDna { id, content } => spawn_blocking(move || {
-            let resp = save_dna::save_dna(id, content).map_err(|e| e.to_string());
-            serialize_resp(request_id, resp)
+            let resp = save_dna::save_dna(id, content).map_err(report(None));
+            serialize_resp(request_id, resp, structured_errors)
         })
         .await
         .unwrap(),
 
         Request::DownloadDna { url } => serialize_resp(
             request_id,
-            download_dna::download_dna(url)
-                .await
-                .map_err(|e| e.to_string()),
+            download_dna::download_dna(url).await.map_err(report(None)),
+            structured_errors,
         ),
-        Request::ConfigurePlayer { id, partial_config } => spawn_blocking(move || {
-            let resp =
-                configure_player::configure_player(id, partial_config).map_err(|e| e.to_string());
-            serialize_resp(request_id, resp)
+        Request::ConfigurePlayer {
+            id,
+            partial_config,
+            holochain_version,
+        } => spawn_blocking(move || {
+            let report = report(Some(&id));
+            let id = player_key(namespace.as_deref(), id);
+            let resp = configure_player::configure_player(
+                id,
+                partial_config,
+                holochain_version,
+                session.id,
+            )
+            .map_err(report);
+            serialize_resp(request_id, resp, structured_errors)
         })
         .await
         .unwrap(),
-        Request::Startup { id, log_level } => spawn_blocking(move || {
-            let resp = startup::startup(id, log_level).map_err(|e| e.to_string());
-            serialize_resp(request_id, resp)
+        Request::Startup {
+            id,
+            log_level,
+            timeout_ms,
+            readiness_probe,
+        } => spawn_blocking(move || {
+            let report = report(Some(&id));
+            let id = player_key(namespace.as_deref(), id);
+            let resp = startup::startup(id, log_level, timeout_ms, readiness_probe).map_err(report);
+            serialize_resp(request_id, resp, structured_errors)
         })
         .await
         .unwrap(),
-        Request::Shutdown { id, signal } => serialize_resp(
-            request_id,
-            shutdown::shutdown(id, signal)
-                .await
-                .map_err(|e| e.to_string()),
-        ),
-        Request::Reset => spawn_blocking(move || serialize_resp(request_id, reset::reset()))
+        Request::Shutdown { id, signal } => {
+            let report = report(Some(&id));
+            let id = player_key(namespace.as_deref(), id);
+            serialize_resp(
+                request_id,
+                shutdown::shutdown(id, signal).await.map_err(report),
+                structured_errors,
+            )
+        }
+        Request::Restart {
+            id,
+            signal,
+            log_level,
+            timeout_ms,
+            readiness_probe,
+        } => {
+            let report = report(Some(&id));
+            let id = player_key(namespace.as_deref(), id);
+            serialize_resp(
+                request_id,
+                restart::restart(id, signal, log_level, timeout_ms, readiness_probe)
+                    .await
+                    .map_err(report),
+                structured_errors,
+            )
+        }
+        Request::Pause { id } => spawn_blocking(move || {
+            let report = report(Some(&id));
+            let id = player_key(namespace.as_deref(), id);
+            serialize_resp(
+                request_id,
+                pause::pause(id).map_err(report),
+                structured_errors,
+            )
+        })
+        .await
+        .unwrap(),
+        Request::Resume { id } => spawn_blocking(move || {
+            let report = report(Some(&id));
+            let id = player_key(namespace.as_deref(), id);
+            serialize_resp(
+                request_id,
+                pause::resume(id).map_err(report),
+                structured_errors,
+            )
+        })
+        .await
+        .unwrap(),
+        Request::RemovePlayer { id, keep_data } => {
+            let report = report(Some(&id));
+            let id = player_key(namespace.as_deref(), id);
+            serialize_resp(
+                request_id,
+                remove_player::remove_player(id, keep_data)
+                    .await
+                    .map_err(report),
+                structured_errors,
+            )
+        }
+        Request::Reset => match namespace {
+            Some(namespace) => {
+                reset::reset_namespace(&namespace).await;
+                serialize_resp(request_id, Ok(()), structured_errors)
+            }
+            None => spawn_blocking(move || {
+                reset::reset();
+                serialize_resp(request_id, Ok(()), structured_errors)
+            })
             .await
             .unwrap(),
-        Request::CallAdminInterface { id, message } => serialize_resp(
-            request_id,
-            admin_call::admin_call(id, message)
-                .await
-                .map_err(|e| e.to_string()),
-        ),
+        },
+        Request::CallAdminInterface {
+            id,
+            message,
+            timeout_ms,
+        } => {
+            // Admin calls can take a while, so respond from a separate task to keep handling
+            // other requests from this client in the meantime.
+            tokio::task::spawn(async move {
+                let report = report(Some(&id));
+                let id = player_key(namespace.as_deref(), id);
+                let response = serialize_resp(
+                    request_id,
+                    admin_call::admin_call(id, message, call_timeout(timeout_ms))
+                        .await
+                        .map_err(report),
+                    structured_errors,
+                );
+                if let Err(e) = ws_write.lock().await.send(Message::Binary(response)).await {
+                    println!("warn: could not send admin call response to client: {}", e);
+                }
+            });
+            return Ok(None);
+        }
         Request::ConnectAppInterface { token, port } => serialize_resp(
             request_id,
             app_interface::connect(token, port, ws_write)
                 .await
-                .map_err(|e| e.to_string()),
+                .map_err(report(None)),
+            structured_errors,
         ),
         Request::DisconnectAppInterface { port } => serialize_resp(
             request_id,
-            app_interface::disconnect_by_port(port)
+            app_interface::disconnect_by_port(port, &ws_write)
                 .await
-                .map_err(|e| e.to_string()),
+                .map_err(report(None)),
+            structured_errors,
         ),
-        Request::CallAppInterface { port, message } => {
-            match app_interface::call(request_id, port, message).await {
+        Request::CallAppInterface {
+            port,
+            message,
+            timeout_ms,
+        } => {
+            match app_interface::call(
+                request_id,
+                structured_errors,
+                port,
+                message,
+                call_timeout(timeout_ms),
+                ws_write,
+            )
+            .await
+            {
                 Ok(()) => return Ok(None),
-                Err(e) => serialize_resp(request_id, Err::<(), _>(e.to_string())),
+                Err(e) => {
+                    serialize_resp(request_id, Err::<(), _>(report(None)(e)), structured_errors)
+                }
             }
         }
         Request::DownloadLogs { id } => spawn_blocking(move || {
+            let report = report(Some(&id));
+            let id = player_key(namespace.as_deref(), id);
+            serialize_resp(
+                request_id,
+                download_logs::download_logs(id).map_err(report),
+                structured_errors,
+            )
+        })
+        .await
+        .unwrap(),
+        Request::ListPlayers => spawn_blocking(move || {
+            serialize_resp(
+                request_id,
+                player_status::list_players(namespace.as_deref()).map_err(report(None)),
+                structured_errors,
+            )
+        })
+        .await
+        .unwrap(),
+        Request::PlayerStatus { id } => spawn_blocking(move || {
+            let report = report(Some(&id));
             serialize_resp(
                 request_id,
-                download_logs::download_logs(id).map_err(|e| e.to_string()),
+                player_status::player_status(namespace.as_deref(), id).map_err(report),
+                structured_errors,
             )
         })
         .await
@@ -261,11 +772,46 @@ async fn ws_message(
     Ok(Some(Message::Binary(response)))
 }
 
-type WsRequestWriter = futures::stream::SplitSink<WebSocketStream<tokio::net::TcpStream>, Message>;
+/// Build an error response to a request that could not be deserialized, for example because the
+/// client was built against a different protocol version. Returns `None` if the message doesn't
+/// even contain a request id to respond to.
+fn unknown_request(
+    bytes: &[u8],
+    source: &rmp_serde::decode::Error,
+    negotiated_structured_errors: bool,
+) -> Option<Vec<u8>> {
+    #[derive(Deserialize)]
+    struct RequestId {
+        id: u64,
+        #[serde(default)]
+        structured_errors: bool,
+    }
+
+    let RequestId {
+        id,
+        structured_errors,
+    } = rmp_serde::from_slice(bytes).ok()?;
 
-type WsResponseWriter = futures::stream::SplitSink<WebSocketStream<tokio::net::TcpStream>, Message>;
+    let error = TryCpError {
+        kind: ErrorKind::InvalidRequest,
+        message: format!(
+            "Could not deserialize request, the client may be using a different TryCP protocol version than this server's version {}: {}",
+            PROTOCOL_VERSION, source
+        ),
+        player_id: None,
+        details: None,
+    };
+    Some(serialize_resp(
+        id,
+        Err::<(), _>(error),
+        structured_errors || negotiated_structured_errors,
+    ))
+}
 
-type WsClientDuplex = WebSocketStream<tokio::net::TcpStream>;
+type WsRequestWriter = futures::stream::SplitSink<WebSocketStream<tokio::net::TcpStream>, Message>;
+
+/// The sending half of a client connection, which may or may not be encrypted.
+type WsResponseWriter = Pin<Box<dyn Sink<Message, Error = tungstenite::Error> + Send>>;
 
 type WsReader = SplitStream<WebSocketStream<tokio::net::TcpStream>>;
 
@@ -273,12 +819,12 @@ type WsReader = SplitStream<WebSocketStream<tokio::net::TcpStream>>;
 #[serde(rename_all = "snake_case", tag = "type")]
 enum HolochainMessage {
     Request {
-        id: usize,
+        id: u64,
         #[serde(with = "serde_bytes")]
         data: Vec<u8>,
     },
     Response {
-        id: usize,
+        id: u64,
         #[serde(with = "serde_bytes")]
         data: Vec<u8>,
     },
@@ -288,8 +834,115 @@ enum HolochainMessage {
     },
 }
 
+fn data_dir() -> &'static Path {
+    DATA_DIR
+        .get()
+        .map(PathBuf::as_path)
+        .unwrap_or_else(|| Path::new(DEFAULT_DATA_DIR_PATH))
+}
+
+fn players_dir() -> PathBuf {
+    data_dir().join(PLAYERS_DIR_NAME)
+}
+
+fn dna_dir() -> PathBuf {
+    data_dir().join(DNA_DIR_NAME)
+}
+
+/// The directory that the directories of players removed with their data kept are moved to.
+fn removed_players_dir() -> PathBuf {
+    data_dir().join(REMOVED_PLAYERS_DIR_NAME)
+}
+
+#[derive(Debug, Snafu)]
+enum PlayerKeyError {
+    #[snafu(display(
+        "Invalid namespace {:?}, namespaces must be non-empty and must not contain '/'",
+        namespace
+    ))]
+    InvalidNamespace { namespace: String },
+    #[snafu(display(
+        "Invalid player id {:?}, player ids must be non-empty, must not contain '/' and must not be {:?}",
+        id,
+        NAMESPACES_DIR_NAME
+    ))]
+    InvalidPlayerId { id: String },
+}
+
+impl ReportError for PlayerKeyError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidRequest
+    }
+}
+
+/// Whether a namespace or player id can be used as a single directory name.
+fn is_path_component(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+fn validate_namespace(namespace: &str) -> Result<(), PlayerKeyError> {
+    snafu::ensure!(is_path_component(namespace), InvalidNamespace { namespace });
+    Ok(())
+}
+
+fn validate_player_id(id: &str) -> Result<(), PlayerKeyError> {
+    snafu::ensure!(
+        is_path_component(id) && id != NAMESPACES_DIR_NAME,
+        InvalidPlayerId { id }
+    );
+    Ok(())
+}
+
+/// The id of the player a request is about, if any.
+fn request_player_id(request: &Request) -> Option<&str> {
+    match request {
+        Request::ConfigurePlayer { id, .. }
+        | Request::Startup { id, .. }
+        | Request::Shutdown { id, .. }
+        | Request::Restart { id, .. }
+        | Request::Pause { id }
+        | Request::Resume { id }
+        | Request::RemovePlayer { id, .. }
+        | Request::CallAdminInterface { id, .. }
+        | Request::DownloadLogs { id }
+        | Request::PlayerStatus { id } => Some(id),
+        _ => None,
+    }
+}
+
+/// The prefix of the keys of all players in a namespace.
+fn namespace_prefix(namespace: &str) -> String {
+    format!("{NAMESPACES_DIR_NAME}/{namespace}/")
+}
+
+/// The key under which a player is stored, which is also the path of its directory relative to
+/// the players directory. Players in a namespace are kept in the namespace's subdirectory of the
+/// [NAMESPACES_DIR_NAME] directory, apart from players without a namespace.
+fn player_key(namespace: Option<&str>, id: String) -> String {
+    match namespace {
+        Some(namespace) => format!("{}{id}", namespace_prefix(namespace)),
+        None => id,
+    }
+}
+
+/// Split a player key into the player's namespace, if any, and id.
+fn split_player_key(key: &str) -> (Option<&str>, &str) {
+    match key
+        .strip_prefix(NAMESPACES_DIR_NAME)
+        .and_then(|key| key.strip_prefix('/'))
+        .and_then(|key| key.split_once('/'))
+    {
+        Some((namespace, id)) => (Some(namespace), id),
+        None => (None, key),
+    }
+}
+
+fn namespace_dir(namespace: &str) -> PathBuf {
+    players_dir().join(NAMESPACES_DIR_NAME).join(namespace)
+}
+
 fn get_player_dir(id: &str) -> PathBuf {
-    Path::new(PLAYERS_DIR_PATH).join(id)
+    players_dir().join(id)
 }
 
 fn player_config_exists(id: &str) -> bool {
@@ -297,27 +950,56 @@ fn player_config_exists(id: &str) -> bool {
     file_path.is_file()
 }
 
+/// Drop the connections to a player's admin interface and to the given app interfaces of the
+/// player's conductor.
+async fn disconnect_player(id: &str, app_ports: impl IntoIterator<Item = u16>) {
+    admin_call::ADMIN_CONNECTIONS.lock().await.remove(id);
+
+    for port in app_ports {
+        let connection = app_interface::APP_CONNECTIONS.lock().await.remove(&port);
+        if let Some(connection) = connection {
+            if let Err(e) = app_interface::disconnect(port, connection).await {
+                println!(
+                    "warn: failed to disconnect app interface at port {}: {}",
+                    port, e
+                );
+            }
+        }
+    }
+}
+
 #[derive(Debug, Snafu)]
 enum KillError {
     #[snafu(display("Could not kill holochain: {}", source))]
     KillHolochain { source: nix::Error },
 }
 
+impl ReportError for KillError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::ProcessControl
+    }
+}
+
 fn kill_player(
     player_cell: &mut Option<PlayerProcesses>,
     id: &str,
     signal: Signal,
-) -> Result<(), KillError> {
+) -> Result<Option<ConductorExitStatus>, KillError> {
     let player = match &mut *player_cell {
         Some(player) => player,
-        None => return Ok(()),
+        None => return Ok(None),
     };
 
     println!("stopping player with id: {}", id);
 
-    signal::kill(Pid::from_raw(player.holochain.id() as i32), signal).context(KillHolochain)?;
-    player.holochain.wait().unwrap();
+    let pid = Pid::from_raw(player.holochain.id() as i32);
+    // A stopped process would not handle the signal until it is continued.
+    if player.paused {
+        signal::kill(pid, Signal::SIGCONT).context(KillHolochain)?;
+    }
+    signal::kill(pid, signal).context(KillHolochain)?;
+    let status = player.holochain.wait().unwrap();
 
     *player_cell = None;
-    Ok(())
+    Ok(Some(watch_conductor::conductor_exit_status(status)))
 }
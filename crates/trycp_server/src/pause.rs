@@ -0,0 +1,64 @@
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+use snafu::{OptionExt, ResultExt, Snafu};
+
+use trycp_api::ErrorKind;
+
+use crate::{ReportError, PLAYERS};
+
+#[derive(Debug, Snafu)]
+pub(crate) enum PauseError {
+    #[snafu(display("Could not find a configuration for player with ID {:?}", id))]
+    PlayerNotConfigured { id: String },
+    #[snafu(display("Conductor of player with ID {:?} is not running", id))]
+    NotRunning { id: String },
+    #[snafu(display("Could not send {} to holochain: {}", signal, source))]
+    SignalHolochain { signal: Signal, source: nix::Error },
+}
+
+impl ReportError for PauseError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::PlayerNotConfigured { .. } => ErrorKind::PlayerNotConfigured,
+            Self::NotRunning { .. } => ErrorKind::ConductorNotRunning,
+            Self::SignalHolochain { .. } => ErrorKind::ProcessControl,
+        }
+    }
+}
+
+/// Freeze a player's conductor, so that it stops responding without losing its state.
+pub(crate) fn pause(id: String) -> Result<(), PauseError> {
+    set_paused(id, true)
+}
+
+/// Thaw a conductor that has been paused.
+pub(crate) fn resume(id: String) -> Result<(), PauseError> {
+    set_paused(id, false)
+}
+
+fn set_paused(id: String, paused: bool) -> Result<(), PauseError> {
+    let players = PLAYERS.read();
+    let player = players
+        .get(&id)
+        .context(PlayerNotConfigured { id: id.clone() })?;
+    let mut processes = player.processes.lock();
+    let processes = processes.as_mut().context(NotRunning { id: id.clone() })?;
+
+    if processes.paused == paused {
+        return Ok(());
+    }
+
+    let signal = if paused {
+        Signal::SIGSTOP
+    } else {
+        Signal::SIGCONT
+    };
+    println!("sending {} to conductor of player {}", signal, id);
+    signal::kill(Pid::from_raw(processes.holochain.id() as i32), signal)
+        .context(SignalHolochain { signal })?;
+    processes.paused = paused;
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use snafu::{ResultExt, Snafu};
+use trycp_api::{ErrorKind, MessageResponse, PlayerStatus, TryCpServerResponse};
+
+use crate::{
+    namespace_prefix, player_config_exists, player_key, split_player_key, Player, ReportError,
+    PLAYERS,
+};
+
+#[derive(Debug, Snafu)]
+pub(crate) enum PlayerStatusError {
+    #[snafu(display("Could not serialize response: {}", source))]
+    SerializeResponse { source: rmp_serde::encode::Error },
+}
+
+impl ReportError for PlayerStatusError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Internal
+    }
+}
+
+pub(crate) fn player_status(
+    namespace: Option<&str>,
+    id: String,
+) -> Result<MessageResponse, PlayerStatusError> {
+    let key = player_key(namespace, id.clone());
+    let status = match PLAYERS.read().get(&key) {
+        Some(player) => status(id, player),
+        None => PlayerStatus {
+            configured: player_config_exists(&key),
+            id,
+            running: false,
+            paused: false,
+            pid: None,
+            admin_port: None,
+            uptime_ms: None,
+            app_ports: Vec::new(),
+            last_exit_status: None,
+        },
+    };
+
+    Ok(MessageResponse::Bytes(
+        rmp_serde::to_vec_named(&TryCpServerResponse::PlayerStatus(status))
+            .context(SerializeResponse)?,
+    ))
+}
+
+/// List the players in the given namespace, or all players if none is given. Players of all
+/// namespaces are listed with ids of the form `namespace/id`.
+pub(crate) fn list_players(namespace: Option<&str>) -> Result<MessageResponse, PlayerStatusError> {
+    let prefix = namespace.map(namespace_prefix);
+    let mut statuses = PLAYERS
+        .read()
+        .iter()
+        .filter_map(|(key, player)| {
+            let id = match &prefix {
+                Some(prefix) => key.strip_prefix(prefix.as_str())?.to_string(),
+                None => match split_player_key(key) {
+                    (Some(namespace), id) => format!("{namespace}/{id}"),
+                    (None, id) => id.to_string(),
+                },
+            };
+            Some(status(id, player))
+        })
+        .collect::<Vec<_>>();
+    statuses.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(MessageResponse::Bytes(
+        rmp_serde::to_vec_named(&TryCpServerResponse::ListPlayers(statuses))
+            .context(SerializeResponse)?,
+    ))
+}
+
+fn status(id: String, player: &Player) -> PlayerStatus {
+    let (pid, uptime_ms, paused) = match &*player.processes.lock() {
+        Some(processes) => (
+            Some(processes.holochain.id()),
+            Some(processes.started_at.elapsed().as_millis() as u64),
+            processes.paused,
+        ),
+        None => (None, None, false),
+    };
+    let mut app_ports = player.app_ports.lock().iter().copied().collect::<Vec<_>>();
+    app_ports.sort_unstable();
+
+    PlayerStatus {
+        id,
+        configured: true,
+        running: pid.is_some(),
+        paused,
+        pid,
+        admin_port: Some(player.admin_port),
+        uptime_ms,
+        app_ports,
+        last_exit_status: player.last_exit_status.lock().clone(),
+    }
+}
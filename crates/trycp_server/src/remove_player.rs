@@ -0,0 +1,89 @@
+use std::{
+    io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use nix::sys::signal::Signal;
+use snafu::{OptionExt, ResultExt, Snafu};
+use tokio::task::spawn_blocking;
+use trycp_api::ErrorKind;
+
+use crate::{
+    disconnect_player, get_player_dir, kill_player, removed_players_dir, KillError, ReportError,
+    FREE_ADMIN_PORTS, PLAYERS,
+};
+
+#[derive(Debug, Snafu)]
+pub(crate) enum RemovePlayerError {
+    #[snafu(display("Could not find a configuration for player with ID {:?}", id))]
+    PlayerNotConfigured { id: String },
+    #[snafu(context(false))]
+    Kill { source: KillError },
+    #[snafu(display("Could not remove player directory at {}: {}", path.display(), source))]
+    RemoveDir { path: PathBuf, source: io::Error },
+    #[snafu(display("Could not move player directory to {}: {}", path.display(), source))]
+    MoveDir { path: PathBuf, source: io::Error },
+}
+
+impl ReportError for RemovePlayerError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::PlayerNotConfigured { .. } => ErrorKind::PlayerNotConfigured,
+            Self::Kill { source } => source.kind(),
+            Self::RemoveDir { .. } | Self::MoveDir { .. } => ErrorKind::Io,
+        }
+    }
+}
+
+/// Shut down a player's conductor, drop its connections and forget about the player, so that its
+/// admin port can be assigned to another player.
+///
+/// A kept player directory is moved to the removed players directory, so that the id can be
+/// configured again.
+pub(crate) async fn remove_player(id: String, keep_data: bool) -> Result<(), RemovePlayerError> {
+    let app_ports = PLAYERS
+        .read()
+        .get(&id)
+        .context(PlayerNotConfigured { id: id.clone() })?
+        .app_ports
+        .lock()
+        .clone();
+    disconnect_player(&id, app_ports).await;
+
+    spawn_blocking(move || -> Result<(), RemovePlayerError> {
+        // Kill the conductor before forgetting about the player, so that a conductor that can't
+        // be killed is still tracked.
+        {
+            let players_guard = PLAYERS.read();
+            let player = players_guard
+                .get(&id)
+                .context(PlayerNotConfigured { id: id.clone() })?;
+            kill_player(&mut player.processes.lock(), &id, Signal::SIGKILL)?;
+        }
+        let player = PLAYERS
+            .write()
+            .remove(&id)
+            .context(PlayerNotConfigured { id: id.clone() })?;
+        FREE_ADMIN_PORTS.lock().insert(player.admin_port);
+
+        let player_dir = get_player_dir(&id);
+        if keep_data {
+            let removed_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            let path = removed_players_dir().join(format!("{id}-{removed_at}"));
+            std::fs::create_dir_all(path.parent().unwrap())
+                .and_then(|()| std::fs::rename(&player_dir, &path))
+                .context(MoveDir { path })?;
+        } else {
+            std::fs::remove_dir_all(&player_dir).context(RemoveDir { path: player_dir })?;
+        }
+
+        println!("removed player with id: {}", id);
+        Ok(())
+    })
+    .await
+    .expect("Task to remove player should have completed")
+}
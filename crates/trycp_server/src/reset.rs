@@ -3,10 +3,11 @@ use std::sync::atomic;
 use nix::sys::signal::Signal;
 
 use crate::{
-    app_interface, kill_player, ADMIN_PORT_RANGE, NEXT_ADMIN_PORT, PLAYERS, PLAYERS_DIR_PATH,
+    admin_port_range, app_interface, kill_player, namespace_dir, namespace_prefix, players_dir,
+    remove_player, FREE_ADMIN_PORTS, NEXT_ADMIN_PORT, PLAYERS,
 };
 
-pub(crate) fn reset() -> Result<(), String> {
+pub(crate) fn reset() {
     let (players, app_connections, admin_connections) = {
         let mut players_guard = PLAYERS.write();
         let mut app_connections_guard =
@@ -21,7 +22,7 @@ pub(crate) fn reset() -> Result<(), String> {
     };
 
     for (port, connection) in app_connections {
-        if let Err(e) = futures::executor::block_on(app_interface::disconnect(connection)) {
+        if let Err(e) = futures::executor::block_on(app_interface::disconnect(port, connection)) {
             println!(
                 "warn: failed to disconnect app interface at port {}: {}",
                 port, e
@@ -41,10 +42,40 @@ pub(crate) fn reset() -> Result<(), String> {
         }
     }
 
-    NEXT_ADMIN_PORT.store(ADMIN_PORT_RANGE.start, atomic::Ordering::SeqCst);
-    if let Err(err) = std::fs::remove_dir_all(PLAYERS_DIR_PATH) {
-        println!("warn: could not remove directory {PLAYERS_DIR_PATH}: {err}");
+    NEXT_ADMIN_PORT.store(admin_port_range().start, atomic::Ordering::SeqCst);
+    FREE_ADMIN_PORTS.lock().clear();
+    let players_dir = players_dir();
+    if let Err(err) = std::fs::remove_dir_all(&players_dir) {
+        println!(
+            "warn: could not remove directory {}: {err}",
+            players_dir.display()
+        );
+    }
+}
+
+/// Remove all players in a namespace, leaving other players untouched.
+pub(crate) async fn reset_namespace(namespace: &str) {
+    let prefix = namespace_prefix(namespace);
+    let keys = PLAYERS
+        .read()
+        .keys()
+        .filter(|key| key.starts_with(&prefix))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for key in keys {
+        if let Err(e) = remove_player::remove_player(key.clone(), false).await {
+            println!("warn: failed to remove player {:?}: {}", key, e);
+        }
     }
 
-    Ok(())
+    let namespace_dir = namespace_dir(namespace);
+    if let Err(err) = std::fs::remove_dir_all(&namespace_dir) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            println!(
+                "warn: could not remove directory {}: {err}",
+                namespace_dir.display()
+            );
+        }
+    }
 }
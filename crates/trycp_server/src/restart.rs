@@ -0,0 +1,104 @@
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use tokio::task::spawn_blocking;
+use trycp_api::{ErrorKind, MessageResponse, ReadinessProbe, RestartResponse, TryCpServerResponse};
+
+use crate::{
+    admin_call::{self, AdminCallError},
+    disconnect_player, kill_player, player_config_exists,
+    shutdown::{self, ShutdownError},
+    startup, KillError, ReportError, PLAYERS,
+};
+
+#[derive(Debug, Snafu)]
+pub(crate) enum RestartError {
+    #[snafu(display("Could not find a configuration for player with ID {:?}", id))]
+    PlayerNotConfigured { id: String },
+    #[snafu(context(false))]
+    Shutdown { source: ShutdownError },
+    #[snafu(context(false))]
+    Kill { source: KillError },
+    #[snafu(context(false))]
+    Startup { source: startup::Error },
+    #[snafu(display("Could not reconnect to admin interface: {}", source))]
+    ReconnectAdmin { source: Box<AdminCallError> },
+    #[snafu(display("Could not serialize response: {}", source))]
+    SerializeResponse { source: rmp_serde::encode::Error },
+}
+
+impl ReportError for RestartError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::PlayerNotConfigured { .. } => ErrorKind::PlayerNotConfigured,
+            Self::Shutdown { source } => source.kind(),
+            Self::Kill { source } => source.kind(),
+            Self::Startup { source } => source.kind(),
+            Self::ReconnectAdmin { source } => source.kind(),
+            Self::SerializeResponse { .. } => ErrorKind::Internal,
+        }
+    }
+}
+
+/// Shut down a player's conductor and start it up again in the same directory, so that its data
+/// is kept. The interfaces of the player are disconnected first, before its processes are locked.
+/// From then on they stay locked until the new conductor has started, so no other request can
+/// observe or start the conductor while it is restarting.
+pub(crate) async fn restart(
+    id: String,
+    signal: Option<String>,
+    log_level: Option<String>,
+    timeout_ms: Option<u64>,
+    readiness_probe: Option<ReadinessProbe>,
+) -> Result<MessageResponse, RestartError> {
+    ensure!(player_config_exists(&id), PlayerNotConfigured { id });
+
+    let signal = shutdown::parse_signal(signal.as_deref())?;
+
+    let app_ports = PLAYERS
+        .read()
+        .get(&id)
+        .map(|player| player.app_ports.lock().clone())
+        .unwrap_or_default();
+    disconnect_player(&id, app_ports).await;
+
+    let pid = spawn_blocking({
+        let id = id.clone();
+        move || -> Result<u32, RestartError> {
+            let players = PLAYERS.read();
+            let player = players
+                .get(&id)
+                .context(PlayerNotConfigured { id: id.clone() })?;
+            let mut processes = player.processes.lock();
+
+            if let Some(status) = kill_player(&mut processes, &id, signal)? {
+                *player.last_exit_status.lock() = Some(status);
+            }
+
+            startup::start_conductor(
+                &id,
+                player,
+                &mut processes,
+                log_level,
+                timeout_ms,
+                readiness_probe,
+            )?;
+
+            Ok(processes
+                .as_ref()
+                .expect("conductor should be running after startup")
+                .holochain
+                .id())
+        }
+    })
+    .await
+    .expect("Task to restart player should have completed")?;
+
+    admin_call::ensure_connected(&id)
+        .await
+        .map_err(Box::new)
+        .context(ReconnectAdmin)?;
+
+    Ok(MessageResponse::Bytes(
+        rmp_serde::to_vec_named(&TryCpServerResponse::Restart(RestartResponse { pid }))
+            .context(SerializeResponse)?,
+    ))
+}
@@ -1,4 +1,4 @@
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 use snafu::{ResultExt, Snafu};
 use std::{
@@ -6,12 +6,14 @@ use std::{
     io::{self, Write},
 };
 
-use crate::DNA_DIR_PATH;
+use trycp_api::ErrorKind;
+
+use crate::{dna_dir, ReportError};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
-    #[snafu(display("Could not create DNA directory at {}: {}", DNA_DIR_PATH, source))]
-    CreateDnaDirectory { source: io::Error },
+    #[snafu(display("Could not create DNA directory at {}: {}", path.display(), source))]
+    CreateDnaDirectory { path: PathBuf, source: io::Error },
     #[snafu(display("Could not create file at {}: {}", path.display(), source))]
     CreateFile { path: PathBuf, source: io::Error },
     #[snafu(display("Could not write {} bytes to file at {}: {}", num_bytes, path.display(), source))]
@@ -22,15 +24,24 @@ pub enum Error {
     },
 }
 
+impl ReportError for Error {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Io
+    }
+}
+
 pub fn save_dna(id: String, content: Vec<u8>) -> Result<String, Error> {
-    let path = Path::new(DNA_DIR_PATH).join(id);
+    let dna_dir = dna_dir();
+    let path = dna_dir.join(id);
 
     let path_string = path
         .to_str()
         .expect("path constructed from UTF-8 filename and UTF-8 directory should be valid UTF-8")
         .to_owned();
 
-    fs::create_dir_all(Path::new(DNA_DIR_PATH)).context(CreateDnaDirectory)?;
+    fs::create_dir_all(&dna_dir).context(CreateDnaDirectory {
+        path: dna_dir.clone(),
+    })?;
 
     let mut file = fs::OpenOptions::new()
         .write(true)
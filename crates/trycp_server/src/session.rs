@@ -0,0 +1,161 @@
+use std::{
+    collections::BTreeSet,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use trycp_api::FEATURES;
+
+use crate::{
+    app_interface, disconnect_player, remove_player, shutdown, split_player_key, WsResponseWriter,
+    CLIENTS, PLAYERS,
+};
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+pub(crate) static ON_DISCONNECT: OnceCell<OnDisconnect> = OnceCell::new();
+
+/// What to do with the players a client configured once its websocket connection ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OnDisconnect {
+    /// Leave the players as they are, to be cleaned up by a later reset.
+    Keep,
+    /// Shut down the players' conductors, keeping their configuration and data.
+    Shutdown,
+    /// Remove the players and their data.
+    ResetSession,
+}
+
+impl FromStr for OnDisconnect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "shutdown" => Ok(Self::Shutdown),
+            "reset-session" => Ok(Self::ResetSession),
+            _ => Err(format!(
+                "expected one of keep, shutdown or reset-session, got {s:?}"
+            )),
+        }
+    }
+}
+
+/// A client's websocket connection. Players are owned by the session that configured them.
+pub(crate) struct Session {
+    pub(crate) id: u64,
+    pub(crate) response_writer: Arc<futures::lock::Mutex<WsResponseWriter>>,
+    /// The session's key in [CLIENTS], once it is authenticated and receives notifications.
+    client_key: Mutex<Option<usize>>,
+    /// The namespaces the session made requests in. Sessions that haven't made any are notified
+    /// about the players of all namespaces.
+    namespaces: Mutex<BTreeSet<String>>,
+    /// The optional features the session negotiated with a hello request.
+    features: Mutex<BTreeSet<String>>,
+}
+
+impl Session {
+    pub(crate) fn new(response_writer: Arc<futures::lock::Mutex<WsResponseWriter>>) -> Self {
+        Self {
+            id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+            response_writer,
+            client_key: Mutex::default(),
+            namespaces: Mutex::default(),
+            features: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn is_authenticated(&self) -> bool {
+        self.client_key.lock().is_some()
+    }
+
+    /// Allow the session to make requests and start sending it notifications.
+    pub(crate) fn authenticate(self: &Arc<Self>) {
+        let mut client_key = self.client_key.lock();
+        if client_key.is_none() {
+            *client_key = Some(CLIENTS.lock().insert(Arc::clone(self)));
+        }
+    }
+
+    /// Use the supported ones of the features the client asked for in a hello request.
+    pub(crate) fn set_features(&self, features: Vec<String>) {
+        *self.features.lock() = features
+            .into_iter()
+            .filter(|feature| FEATURES.contains(&feature.as_str()))
+            .collect();
+    }
+
+    pub(crate) fn has_feature(&self, feature: &str) -> bool {
+        self.features.lock().contains(feature)
+    }
+
+    /// Notify the session about the players of a namespace it made a request in.
+    pub(crate) fn enter_namespace(&self, namespace: &str) {
+        let mut namespaces = self.namespaces.lock();
+        if !namespaces.contains(namespace) {
+            namespaces.insert(namespace.to_string());
+        }
+    }
+
+    /// The id by which the session knows the player with the given key, if it is notified about
+    /// the player at all.
+    ///
+    /// Sessions in namespaces are only notified about players of those namespaces, by their id
+    /// within the namespace. Other sessions are notified about all players, with the ids of
+    /// players in a namespace given as `namespace/id`.
+    pub(crate) fn notification_id(&self, key: &str) -> Option<String> {
+        let namespaces = self.namespaces.lock();
+        match split_player_key(key) {
+            (Some(namespace), id) if namespaces.contains(namespace) => Some(id.to_string()),
+            (Some(namespace), id) if namespaces.is_empty() => Some(format!("{namespace}/{id}")),
+            (None, id) if namespaces.is_empty() => Some(id.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Stop sending a closed session anything and apply the `--on-disconnect` policy to the players
+/// it configured.
+pub(crate) async fn end_session(session: &Session) {
+    if let Some(client_key) = session.client_key.lock().take() {
+        CLIENTS.lock().remove(client_key);
+    }
+    app_interface::unsubscribe(&session.response_writer).await;
+
+    let on_disconnect = *ON_DISCONNECT
+        .get()
+        .expect("on disconnect policy should have been set");
+    if on_disconnect == OnDisconnect::Keep {
+        return;
+    }
+
+    let players = PLAYERS
+        .read()
+        .iter()
+        .filter(|(_, player)| player.session == session.id)
+        .map(|(id, player)| (id.clone(), player.app_ports.lock().clone()))
+        .collect::<Vec<_>>();
+
+    for (id, app_ports) in players {
+        println!("cleaning up player {} of closed session {}", id, session.id);
+        let result = match on_disconnect {
+            OnDisconnect::Keep => Ok(()),
+            OnDisconnect::Shutdown => {
+                disconnect_player(&id, app_ports).await;
+                shutdown::shutdown(id.clone(), None)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            OnDisconnect::ResetSession => remove_player::remove_player(id.clone(), false)
+                .await
+                .map_err(|e| e.to_string()),
+        };
+        if let Err(e) = result {
+            println!("warn: could not clean up player {}: {}", id, e);
+        }
+    }
+}
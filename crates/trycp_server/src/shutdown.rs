@@ -1,8 +1,9 @@
 use crate::admin_call::ADMIN_CONNECTIONS;
-use crate::{kill_player, player_config_exists, KillError, PLAYERS};
+use crate::{kill_player, player_config_exists, KillError, ReportError, PLAYERS};
 use nix::sys::signal::Signal;
 use snafu::{ensure, Snafu};
 use tokio::task::spawn_blocking;
+use trycp_api::ErrorKind;
 
 #[derive(Debug, Snafu)]
 pub(crate) enum ShutdownError {
@@ -14,32 +15,35 @@ pub(crate) enum ShutdownError {
     Kill { source: KillError },
 }
 
+impl ReportError for ShutdownError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::PlayerNotConfigured { .. } => ErrorKind::PlayerNotConfigured,
+            Self::UnrecognizedSignal { .. } => ErrorKind::InvalidRequest,
+            Self::Kill { source } => source.kind(),
+        }
+    }
+}
+
 pub(crate) async fn shutdown(id: String, signal: Option<String>) -> Result<(), ShutdownError> {
     ensure!(player_config_exists(&id), PlayerNotConfigured { id });
 
     ADMIN_CONNECTIONS.lock().await.remove(&id);
 
-    let signal = match signal.as_deref() {
-        Some("SIGTERM") | None => Signal::SIGTERM,
-        Some("SIGKILL") => Signal::SIGKILL,
-        Some("SIGINT") => Signal::SIGINT,
-        Some(s) => {
-            return Err(ShutdownError::UnrecognizedSignal {
-                signal: s.to_owned(),
-            })
-        }
-    };
+    let signal = parse_signal(signal.as_deref())?;
 
     spawn_blocking(move || -> Result<(), ShutdownError> {
         let players_guard = PLAYERS.read();
-        let processes_lock = match players_guard.get(&id) {
-            Some(player) => &player.processes,
+        let player = match players_guard.get(&id) {
+            Some(player) => player,
             None => return Ok(()),
         };
 
-        let mut player_cell = processes_lock.lock();
+        let mut player_cell = player.processes.lock();
 
-        kill_player(&mut player_cell, &id, signal)?;
+        if let Some(status) = kill_player(&mut player_cell, &id, signal)? {
+            *player.last_exit_status.lock() = Some(status);
+        }
 
         Ok(())
     })
@@ -48,3 +52,15 @@ pub(crate) async fn shutdown(id: String, signal: Option<String>) -> Result<(), S
 
     Ok(())
 }
+
+/// Parse the name of a signal with which a conductor may be shut down, defaulting to SIGTERM.
+pub(crate) fn parse_signal(signal: Option<&str>) -> Result<Signal, ShutdownError> {
+    match signal {
+        Some("SIGTERM") | None => Ok(Signal::SIGTERM),
+        Some("SIGKILL") => Ok(Signal::SIGKILL),
+        Some("SIGINT") => Ok(Signal::SIGINT),
+        Some(s) => Err(ShutdownError::UnrecognizedSignal {
+            signal: s.to_owned(),
+        }),
+    }
+}
@@ -1,15 +1,18 @@
 use crate::{
-    get_player_dir, PlayerProcesses, CONDUCTOR_CONFIG_FILENAME, CONDUCTOR_MAGIC_STRING,
-    CONDUCTOR_STDERR_LOG_FILENAME, CONDUCTOR_STDOUT_LOG_FILENAME, LAIR_PASSPHRASE, PLAYERS,
+    admin_call, get_player_dir, watch_conductor::watch_conductor, Player, PlayerProcesses,
+    ReportError, CONDUCTOR_CONFIG_FILENAME, CONDUCTOR_MAGIC_STRING, CONDUCTOR_STDERR_LOG_FILENAME,
+    CONDUCTOR_STDOUT_LOG_FILENAME, LAIR_PASSPHRASE, PLAYERS,
 };
 use snafu::{OptionExt, ResultExt, Snafu};
 use std::io::Lines;
 use std::process::ChildStdout;
+use std::time::Duration;
 use std::{
     io::{self, BufRead, BufReader, Write},
     path::PathBuf,
     process::{Command, Stdio},
 };
+use trycp_api::{ErrorKind, ReadinessProbe};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -23,10 +26,26 @@ pub enum Error {
     HolochainStartupFailed { reason: String },
 }
 
-pub fn startup(id: String, log_level: Option<String>) -> Result<(), Error> {
-    let rust_log = log_level.unwrap_or_else(|| "error".to_string());
+impl ReportError for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::PlayerNotConfigured { .. } => ErrorKind::PlayerNotConfigured,
+            Self::SpawnLair { .. }
+            | Self::SpawnHolochain { .. }
+            | Self::HolochainStartupFailed { .. } => ErrorKind::StartupFailed,
+        }
+    }
+}
+
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+const ADMIN_INTERFACE_POLL_INTERVAL: Duration = Duration::from_millis(200);
 
-    let player_dir = get_player_dir(&id);
+pub fn startup(
+    id: String,
+    log_level: Option<String>,
+    timeout_ms: Option<u64>,
+    readiness_probe: Option<ReadinessProbe>,
+) -> Result<(), Error> {
     let players = PLAYERS.read();
     let player = players
         .get(&id)
@@ -38,9 +57,36 @@ pub fn startup(id: String, log_level: Option<String>) -> Result<(), Error> {
         return Ok(());
     }
 
+    start_conductor(
+        &id,
+        player,
+        &mut processes,
+        log_level,
+        timeout_ms,
+        readiness_probe,
+    )
+}
+
+/// Start a player's conductor and wait for it to become ready. The caller must hold the lock on
+/// the player's processes, which must be empty.
+pub(crate) fn start_conductor(
+    id: &str,
+    player: &Player,
+    processes: &mut Option<PlayerProcesses>,
+    log_level: Option<String>,
+    timeout_ms: Option<u64>,
+    readiness_probe: Option<ReadinessProbe>,
+) -> Result<(), Error> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_STARTUP_TIMEOUT);
+    let rust_log = log_level.unwrap_or_else(|| "error".to_string());
+
+    let player_dir = get_player_dir(id);
+
     println!("starting player with id: {}", id);
 
-    let mut conductor = Command::new("holochain")
+    let mut conductor = Command::new(&player.holochain_bin)
         .current_dir(&player_dir)
         .arg("--piped")
         .arg("-c")
@@ -79,22 +125,27 @@ pub fn startup(id: String, log_level: Option<String>) -> Result<(), Error> {
         format!("holochain({id})"),
     );
 
-    let error = match futures::executor::block_on(tokio::time::timeout(
-        std::time::Duration::from_secs(10),
-        ready_rx,
-    )) {
+    let admin_port = player.admin_port;
+    let error = match futures::executor::block_on(tokio::time::timeout(timeout, async move {
+        match readiness_probe.unwrap_or_default() {
+            ReadinessProbe::LogLine => match ready_rx.await {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(())) => Err("Holochain ready message not found.".to_string()),
+                Err(err) => Err(err.to_string()),
+            },
+            ReadinessProbe::AdminInterface => wait_for_admin_interface(admin_port, ready_rx).await,
+        }
+    })) {
         Err(err) => Err::<(), _>(Error::HolochainStartupFailed {
             reason: err.to_string(),
         }),
-        Ok(Err(err)) => Err(Error::HolochainStartupFailed {
-            reason: err.to_string(),
-        }),
-        Ok(Ok(Err(()))) => Err(Error::HolochainStartupFailed {
-            reason: "Holochain ready message not found.".to_string(),
-        }),
-        Ok(Ok(Ok(()))) => {
+        Ok(Err(reason)) => Err(Error::HolochainStartupFailed { reason }),
+        Ok(Ok(())) => {
+            watch_conductor(id.to_string(), conductor.id());
             *processes = Some(PlayerProcesses {
                 holochain: conductor,
+                started_at: std::time::Instant::now(),
+                paused: false,
             });
 
             println!("conductor started up for {}", id);
@@ -109,6 +160,26 @@ pub fn startup(id: String, log_level: Option<String>) -> Result<(), Error> {
     Err(error)
 }
 
+/// Poll the admin interface until it accepts a websocket connection, giving up if the conductor
+/// closes its stdout in the meantime.
+async fn wait_for_admin_interface(
+    admin_port: u16,
+    mut stdout_closed_rx: tokio::sync::oneshot::Receiver<Result<(), ()>>,
+) -> Result<(), String> {
+    loop {
+        if let Ok(mut ws_stream) = admin_call::connect(admin_port).await {
+            let _ = ws_stream.close(None).await;
+            return Ok(());
+        }
+        if let Ok(Err(())) = stdout_closed_rx.try_recv() {
+            return Err(
+                "Holochain exited before its admin interface accepted connections.".to_string(),
+            );
+        }
+        tokio::time::sleep(ADMIN_INTERFACE_POLL_INTERVAL).await;
+    }
+}
+
 fn open_log_file(path: PathBuf) -> io::Result<std::fs::File> {
     std::fs::OpenOptions::new()
         .create(true)
@@ -126,9 +197,9 @@ fn log_stdout(
 
     let (tx, rx) = tokio::sync::oneshot::channel();
     let mut tx = Some(tx);
-    tokio::spawn({
+    tokio::task::spawn_blocking({
         let ready_line = ready_line.to_string();
-        async move {
+        move || {
             let mut reader = BufReader::new(stdout).lines();
             while let Some(Ok(line)) = reader.next() {
                 if let Some(f) = &mut f {
@@ -0,0 +1,44 @@
+use std::{fs::File, io, io::BufReader, path::PathBuf, sync::Arc};
+
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use tokio_rustls::{rustls, TlsAcceptor};
+
+#[derive(Debug, Snafu)]
+pub(crate) enum LoadTlsError {
+    #[snafu(display("Could not read TLS certificate file {}: {}", path.display(), source))]
+    ReadCertFile { path: PathBuf, source: io::Error },
+    #[snafu(display("TLS certificate file {} does not contain any certificates", path.display()))]
+    NoCerts { path: PathBuf },
+    #[snafu(display("Could not read TLS key file {}: {}", path.display(), source))]
+    ReadKeyFile { path: PathBuf, source: io::Error },
+    #[snafu(display("TLS key file {} does not contain a private key", path.display()))]
+    NoKey { path: PathBuf },
+    #[snafu(display("Invalid TLS certificate or key: {}", source))]
+    InvalidCertOrKey { source: rustls::Error },
+}
+
+/// Build the acceptor that serves `wss://` connections from a PEM encoded certificate chain and
+/// private key.
+pub(crate) fn load_acceptor(
+    cert_path: PathBuf,
+    key_path: PathBuf,
+) -> Result<TlsAcceptor, LoadTlsError> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(
+        File::open(&cert_path).context(ReadCertFile { path: &cert_path })?,
+    ))
+    .collect::<Result<Vec<_>, _>>()
+    .context(ReadCertFile { path: &cert_path })?;
+    ensure!(!certs.is_empty(), NoCerts { path: &cert_path });
+
+    let key = rustls_pemfile::private_key(&mut BufReader::new(
+        File::open(&key_path).context(ReadKeyFile { path: &key_path })?,
+    ))
+    .context(ReadKeyFile { path: &key_path })?
+    .context(NoKey { path: &key_path })?;
+
+    let config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context(InvalidCertOrKey)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
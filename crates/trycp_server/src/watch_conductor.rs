@@ -0,0 +1,97 @@
+use std::{
+    collections::HashSet, os::unix::process::ExitStatusExt, process::ExitStatus, time::Duration,
+};
+
+use tokio::task::spawn_blocking;
+use trycp_api::ConductorExitStatus;
+
+use crate::{broadcast_player_event, disconnect_player, MessageToClient, PLAYERS};
+
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watch a started conductor until it exits. If it exits without having been shut down through
+/// the server, its connections are dropped and the clients of its namespace that asked for
+/// `conductor_exit_notifications` are notified.
+///
+/// The watcher stops as soon as the player's processes no longer belong to the conductor with
+/// the given pid, which is the case once it has been shut down or restarted.
+pub(crate) fn watch_conductor(id: String, pid: u32) {
+    tokio::spawn(async move {
+        let (status, app_ports) = loop {
+            tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+
+            // The player locks are held by startups and restarts until their conductor is ready,
+            // so they are only taken on a blocking thread.
+            let poll = spawn_blocking({
+                let id = id.clone();
+                move || poll_conductor(&id, pid)
+            })
+            .await
+            .unwrap();
+            match poll {
+                Poll::Running => continue,
+                Poll::Exited { status, app_ports } => break (status, app_ports),
+                Poll::Stopped => return,
+            }
+        };
+
+        println!("conductor of player {} exited with {}", id, status);
+
+        disconnect_player(&id, app_ports).await;
+
+        broadcast_player_event(&id, "conductor_exit_notifications", |id| {
+            MessageToClient::ConductorExited {
+                id,
+                status: status.clone(),
+            }
+        })
+        .await;
+    });
+}
+
+enum Poll {
+    Running,
+    Exited {
+        status: ConductorExitStatus,
+        app_ports: HashSet<u16>,
+    },
+    /// The conductor is no longer watched.
+    Stopped,
+}
+
+/// Check whether the watched conductor has exited, and if so, record its exit status.
+fn poll_conductor(id: &str, pid: u32) -> Poll {
+    let players = PLAYERS.read();
+    let player = match players.get(id) {
+        Some(player) => player,
+        None => return Poll::Stopped,
+    };
+    let mut processes = player.processes.lock();
+    let holochain = match &mut *processes {
+        Some(processes) if processes.holochain.id() == pid => &mut processes.holochain,
+        _ => return Poll::Stopped,
+    };
+    match holochain.try_wait() {
+        Ok(None) => Poll::Running,
+        Ok(Some(status)) => {
+            let status = conductor_exit_status(status);
+            *processes = None;
+            *player.last_exit_status.lock() = Some(status.clone());
+            Poll::Exited {
+                status,
+                app_ports: player.app_ports.lock().clone(),
+            }
+        }
+        Err(e) => {
+            println!("warn: could not check conductor of player {}: {}", id, e);
+            Poll::Stopped
+        }
+    }
+}
+
+pub(crate) fn conductor_exit_status(status: ExitStatus) -> ConductorExitStatus {
+    ConductorExitStatus {
+        code: status.code(),
+        signal: status.signal(),
+    }
+}
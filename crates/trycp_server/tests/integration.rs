@@ -1,12 +1,18 @@
 use std::process::Stdio;
 
-use holochain_conductor_api::conductor::ConductorConfig;
+use futures::{SinkExt, StreamExt};
+use holochain_conductor_api::{conductor::ConductorConfig, AdminRequest, AdminResponse};
+use holochain_types::websocket::AllowedOrigins;
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
     process::Child,
     sync::oneshot::Receiver,
 };
-use trycp_api::Request;
+use tokio_tungstenite::tungstenite::Message;
+use trycp_api::{
+    ErrorKind, MessageToClient, PlayerStatus, ReadinessProbe, Request, RequestWrapper, TryCpError,
+    TryCpServerResponse, PROTOCOL_VERSION,
+};
 
 const ONE_MIN: std::time::Duration = std::time::Duration::from_secs(60);
 
@@ -35,6 +41,7 @@ async fn multiple_conductors_on_same_machine_are_assigned_different_admin_ports(
     network_seed: ~
     no_dpki: true"
                         .to_string(),
+                    holochain_version: None,
                 },
                 ONE_MIN,
             )
@@ -65,6 +72,8 @@ async fn multiple_conductors_on_same_machine_are_assigned_different_admin_ports(
             Request::Startup {
                 id: id_player_1.to_string(),
                 log_level: None,
+                timeout_ms: None,
+                readiness_probe: None,
             },
             ONE_MIN,
         )
@@ -89,6 +98,7 @@ async fn multiple_conductors_on_same_machine_are_assigned_different_admin_ports(
     network_seed: ~
     no_dpki: true"
                         .to_string(),
+                    holochain_version: None,
                 },
                 ONE_MIN,
             )
@@ -126,7 +136,2175 @@ async fn multiple_conductors_on_same_machine_are_assigned_different_admin_ports(
         .unwrap();
 }
 
-async fn start_server(port: u16) -> (tokio::process::Child, Receiver<String>) {
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_ports_are_assigned_from_configured_range() {
+    let port = 9002;
+
+    let (_trycp_server, config_path_rx) = start_server_with_args(
+        port,
+        &["--bind", "127.0.0.1", "--admin-port-range", "9300..9301"],
+    )
+    .await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://127.0.0.1:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+
+    let config = serde_yaml::from_str::<ConductorConfig>(
+        &std::fs::read_to_string(config_path_rx.await.unwrap()).unwrap(),
+    )
+    .unwrap();
+    let admin_port = config
+        .admin_interfaces
+        .unwrap()
+        .first()
+        .unwrap()
+        .driver
+        .port();
+    assert_eq!(admin_port, 9300);
+
+    // The only port in the range is taken, so a second player cannot be configured.
+    let err = trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_2".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Ran out of possible admin ports"));
+
+    trycp_client.request(Request::Reset, ONE_MIN).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn players_can_only_select_registered_holochain_versions() {
+    let port = 9003;
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--holochain-version", "custom=/usr/bin/holochain"]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    let err = trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: Some("unknown".to_string()),
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap_err();
+    let err = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<TryCpError>())
+        .unwrap();
+    assert_eq!(err.kind, ErrorKind::UnknownHolochainVersion);
+    assert_eq!(err.player_id.as_deref(), Some("player_1"));
+    assert!(err
+        .message
+        .contains("No holochain binary is registered for version \"unknown\""));
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: Some("custom".to_string()),
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+
+    trycp_client.request(Request::Reset, ONE_MIN).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn startup_fails_when_conductor_is_not_ready_before_timeout() {
+    let port = 9004;
+    let holochain_bin = fake_holochain(port, "exec sleep 600");
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--holochain-bin", holochain_bin.to_str().unwrap()]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+
+    let started_at = std::time::Instant::now();
+    let err = trycp_client
+        .request(
+            Request::Startup {
+                id: "player_1".to_string(),
+                log_level: None,
+                timeout_ms: Some(500),
+                readiness_probe: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("deadline has elapsed"), "{err}");
+    assert!(started_at.elapsed() < std::time::Duration::from_secs(10));
+
+    trycp_client.request(Request::Reset, ONE_MIN).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clients_are_notified_when_a_conductor_exits() {
+    let port = 9005;
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nsleep 1\nexit 3");
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--holochain-bin", holochain_bin.to_str().unwrap()]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    let mut conductor_exits = trycp_client.subscribe_conductor_exits();
+
+    // Clients that don't ask for notifications in a hello, like older TypeScript clients, don't
+    // know about them and don't receive any.
+    let (mut legacy_client, _) = tokio_tungstenite::connect_async(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    trycp_client
+        .request(
+            Request::Startup {
+                id: "player_1".to_string(),
+                log_level: None,
+                timeout_ms: None,
+                readiness_probe: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+
+    let exit = tokio::time::timeout(ONE_MIN, conductor_exits.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(exit.id, "player_1");
+    assert_eq!(exit.status.code, Some(3));
+    assert!(tokio::time::timeout(
+        std::time::Duration::from_secs(1),
+        futures::StreamExt::next(&mut legacy_client)
+    )
+    .await
+    .is_err());
+
+    trycp_client.request(Request::Reset, ONE_MIN).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn player_status_reflects_conductor_lifecycle() {
+    let port = 9006;
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nexec sleep 600");
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--holochain-bin", holochain_bin.to_str().unwrap()]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    let status = player_status(&trycp_client, "player_1").await;
+    assert!(!status.configured);
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+
+    let status = player_status(&trycp_client, "player_1").await;
+    assert!(status.configured);
+    assert!(!status.running);
+    assert!(status.admin_port.is_some());
+
+    trycp_client
+        .request(
+            Request::Startup {
+                id: "player_1".to_string(),
+                log_level: None,
+                timeout_ms: None,
+                readiness_probe: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+
+    let status = player_status(&trycp_client, "player_1").await;
+    assert!(status.running);
+    assert!(status.pid.is_some());
+    assert!(status.uptime_ms.is_some());
+
+    let response = trycp_client
+        .request(Request::ListPlayers, ONE_MIN)
+        .await
+        .unwrap();
+    match rmp_serde::from_slice(&response.into_bytes()).unwrap() {
+        TryCpServerResponse::ListPlayers(players) => {
+            assert_eq!(players.len(), 1);
+            assert_eq!(players[0].id, "player_1");
+            assert_eq!(players[0].pid, status.pid);
+        }
+        response => panic!("unexpected response {response:?}"),
+    }
+
+    trycp_client
+        .request(
+            Request::Shutdown {
+                id: "player_1".to_string(),
+                signal: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+
+    let status = player_status(&trycp_client, "player_1").await;
+    assert!(!status.running);
+    assert_eq!(
+        status.last_exit_status.and_then(|status| status.signal),
+        Some(15)
+    );
+
+    trycp_client.request(Request::Reset, ONE_MIN).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn typed_client_methods_decode_responses() {
+    let port = 9020;
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nexec sleep 600");
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--holochain-bin", holochain_bin.to_str().unwrap()]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    let path = trycp_client
+        .save_dna("typed.dna", vec![1, 2, 3])
+        .await
+        .unwrap();
+    assert!(path.ends_with("typed.dna"));
+    assert_eq!(std::fs::read(&path).unwrap(), vec![1, 2, 3]);
+
+    trycp_client
+        .configure_player("player_1", "", None)
+        .await
+        .unwrap();
+    trycp_client
+        .startup("player_1", None, None, None)
+        .await
+        .unwrap();
+    let status = trycp_client.player_status("player_1").await.unwrap();
+    assert!(status.running);
+
+    trycp_client.pause("player_1").await.unwrap();
+    assert!(trycp_client.player_status("player_1").await.unwrap().paused);
+    trycp_client.resume("player_1").await.unwrap();
+
+    let logs = trycp_client.download_logs("player_1").await.unwrap();
+    assert!(String::from_utf8_lossy(&logs.conductor_stdout).contains("Conductor ready."));
+
+    trycp_client.shutdown("player_1", None).await.unwrap();
+    trycp_client.remove_player("player_1", false).await.unwrap();
+    assert!(trycp_client.list_players().await.unwrap().is_empty());
+
+    let err = trycp_client
+        .startup("player_1", None, None, None)
+        .await
+        .unwrap_err();
+    let err = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<TryCpError>())
+        .unwrap();
+    assert_eq!(err.kind, ErrorKind::PlayerNotConfigured);
+
+    trycp_client.reset().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restarted_conductor_accepts_admin_calls() {
+    let port = 9007;
+    let id = "player_1";
+
+    let (_trycp_server, _) = start_server(port).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: id.to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    trycp_client
+        .request(
+            Request::Startup {
+                id: id.to_string(),
+                log_level: None,
+                timeout_ms: None,
+                readiness_probe: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    let pid_before = player_status(&trycp_client, id).await.pid.unwrap();
+
+    // Attach an app interface, which the conductor keeps in its state across restarts.
+    let app_port = 9535;
+    let response = trycp_client
+        .admin_request(
+            id,
+            AdminRequest::AttachAppInterface {
+                port: Some(app_port),
+                allowed_origins: AllowedOrigins::Any,
+                installed_app_id: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        response,
+        AdminResponse::AppInterfaceAttached { .. }
+    ));
+
+    let restart = trycp_client
+        .restart(
+            id,
+            None,
+            None,
+            Some(30_000),
+            Some(ReadinessProbe::AdminInterface),
+        )
+        .await
+        .unwrap();
+    assert_ne!(pid_before, restart.pid);
+
+    let response = trycp_client
+        .admin_request(id, AdminRequest::ListAppInterfaces)
+        .await
+        .unwrap();
+    let AdminResponse::AppInterfacesListed(interfaces) = response else {
+        panic!("unexpected response {response:?}");
+    };
+    assert!(interfaces
+        .iter()
+        .any(|interface| interface.port == app_port));
+
+    trycp_client.request(Request::Reset, ONE_MIN).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conductors_are_ready_once_their_admin_interface_accepts_connections() {
+    let port = 9030;
+    let id = "player_1";
+    // The fake conductor never logs that it is ready.
+    let holochain_bin = fake_holochain(port, "exec sleep 600");
+
+    let (_trycp_server, _) = start_server_with_args(
+        port,
+        &[
+            "--holochain-bin",
+            holochain_bin.to_str().unwrap(),
+            "--admin-port-range",
+            "9536..9537",
+        ],
+    )
+    .await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    trycp_client.configure_player(id, "", None).await.unwrap();
+
+    // The admin interface only starts listening after the server has polled it a few times.
+    tokio::spawn(async {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        fake_interface(
+            tokio::net::TcpListener::bind(("localhost", 9536))
+                .await
+                .unwrap(),
+            |_| unreachable!("no admin calls are made"),
+        );
+    });
+    trycp_client
+        .startup(id, None, Some(10_000), Some(ReadinessProbe::AdminInterface))
+        .await
+        .unwrap();
+    assert!(player_status(&trycp_client, id).await.running);
+
+    trycp_client.reset().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn paused_conductors_are_stopped_until_resumed() {
+    let port = 9008;
+    let id = "player_1";
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nexec sleep 600");
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--holochain-bin", holochain_bin.to_str().unwrap()]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: id.to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    trycp_client
+        .request(
+            Request::Startup {
+                id: id.to_string(),
+                log_level: None,
+                timeout_ms: None,
+                readiness_probe: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    let pid = player_status(&trycp_client, id).await.pid.unwrap();
+
+    trycp_client
+        .request(Request::Pause { id: id.to_string() }, ONE_MIN)
+        .await
+        .unwrap();
+    assert!(player_status(&trycp_client, id).await.paused);
+    assert!(wait_for_process_state(pid, |state| state == 'T').await);
+
+    trycp_client
+        .request(Request::Resume { id: id.to_string() }, ONE_MIN)
+        .await
+        .unwrap();
+    assert!(!player_status(&trycp_client, id).await.paused);
+    assert!(wait_for_process_state(pid, |state| state != 'T').await);
+
+    // A paused conductor can still be shut down.
+    trycp_client
+        .request(Request::Pause { id: id.to_string() }, ONE_MIN)
+        .await
+        .unwrap();
+    trycp_client
+        .request(
+            Request::Shutdown {
+                id: id.to_string(),
+                signal: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    assert!(!player_status(&trycp_client, id).await.running);
+
+    trycp_client.request(Request::Reset, ONE_MIN).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn startup_only_removes_the_servers_own_data() {
+    let port = 9029;
+    let data_dir = std::env::temp_dir().join(format!("trycp-{port}"));
+    std::fs::create_dir_all(data_dir.join("players").join("player_1")).unwrap();
+    std::fs::create_dir_all(data_dir.join("dnas")).unwrap();
+    std::fs::write(data_dir.join("dnas").join("dna"), b"dna").unwrap();
+    std::fs::write(data_dir.join("other"), b"other").unwrap();
+
+    let (_trycp_server, _) = start_server(port).await;
+
+    assert!(!data_dir.join("players").exists());
+    assert!(!data_dir.join("dnas").exists());
+    assert_eq!(std::fs::read(data_dir.join("other")).unwrap(), b"other");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn removed_players_free_their_admin_port() {
+    let port = 9009;
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nexec sleep 600");
+
+    let (_trycp_server, config_path_rx) = start_server_with_args(
+        port,
+        &[
+            "--holochain-bin",
+            holochain_bin.to_str().unwrap(),
+            "--admin-port-range",
+            "9400..9401",
+        ],
+    )
+    .await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    let config_path = config_path_rx.await.unwrap();
+    trycp_client
+        .request(
+            Request::Startup {
+                id: "player_1".to_string(),
+                log_level: None,
+                timeout_ms: None,
+                readiness_probe: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    let pid = player_status(&trycp_client, "player_1").await.pid.unwrap();
+
+    trycp_client
+        .request(
+            Request::RemovePlayer {
+                id: "player_1".to_string(),
+                keep_data: false,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    assert!(!player_status(&trycp_client, "player_1").await.configured);
+    assert!(!std::path::Path::new(&config_path).exists());
+    assert!(!std::path::Path::new(&format!("/proc/{pid}")).exists());
+
+    // The only admin port in the range can be assigned to a new player.
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_2".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        player_status(&trycp_client, "player_2").await.admin_port,
+        Some(9400)
+    );
+
+    // Kept data is moved out of the way, so that the player can be configured again.
+    trycp_client.remove_player("player_2", true).await.unwrap();
+    assert!(!player_status(&trycp_client, "player_2").await.configured);
+    let removed_players_dir = std::env::temp_dir().join(format!("trycp-{port}/removed-players"));
+    assert!(std::fs::read_dir(&removed_players_dir)
+        .unwrap()
+        .any(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_str()
+            .unwrap()
+            .starts_with("player_2-")));
+    trycp_client
+        .configure_player("player_2", "", None)
+        .await
+        .unwrap();
+
+    trycp_client.request(Request::Reset, ONE_MIN).await.unwrap();
+}
+
+/// Wait until the state of a process, as reported in `/proc/<pid>/stat`, satisfies the predicate.
+/// Signals are delivered asynchronously, so the state may not change right away.
+async fn wait_for_process_state(pid: u32, predicate: impl Fn(char) -> bool) -> bool {
+    for _ in 0..50 {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).unwrap();
+        let (_, after_name) = stat.rsplit_once(')').unwrap();
+        if predicate(after_name.trim_start().chars().next().unwrap()) {
+            return true;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clients_discover_server_capabilities_on_connect() {
+    let port = 9010;
+    let holochain_bin = fake_holochain(port, "exec sleep 600");
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--holochain-bin", holochain_bin.to_str().unwrap()]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    let server_info = trycp_client.server_info();
+    assert_eq!(server_info.protocol_version, PROTOCOL_VERSION);
+    assert_eq!(server_info.server_version, env!("CARGO_PKG_VERSION"));
+    assert!(server_info
+        .request_types
+        .iter()
+        .any(|t| t == "list_players"));
+    assert!(server_info
+        .features
+        .iter()
+        .any(|f| f == "structured_errors"));
+    assert_eq!(server_info.holochain_version.as_deref(), Some("0.0.0-fake"));
+
+    let err = trycp_client
+        .request(
+            Request::Hello {
+                client_version: PROTOCOL_VERSION + 1,
+                features: Vec::new(),
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap_err();
+    let err = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<TryCpError>())
+        .unwrap();
+    assert_eq!(err.kind, ErrorKind::ProtocolMismatch);
+
+    // Clients that ask for structured errors in their hello receive them for every request.
+    let (mut raw_client, _) = tokio_tungstenite::connect_async(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    let requests = [
+        Request::Hello {
+            client_version: PROTOCOL_VERSION,
+            features: vec!["structured_errors".to_string()],
+        },
+        Request::Startup {
+            id: "player_1".to_string(),
+            log_level: None,
+            timeout_ms: None,
+            readiness_probe: None,
+        },
+    ];
+    let mut responses = Vec::new();
+    for (id, request) in requests.into_iter().enumerate() {
+        let request = RequestWrapper {
+            id: id as u64,
+            request,
+            structured_errors: false,
+            namespace: None,
+        };
+        futures::SinkExt::send(
+            &mut raw_client,
+            Message::Binary(rmp_serde::to_vec_named(&request).unwrap()),
+        )
+        .await
+        .unwrap();
+        let response = futures::StreamExt::next(&mut raw_client)
+            .await
+            .unwrap()
+            .unwrap();
+        responses.push(rmp_serde::from_slice::<MessageToClient>(&response.into_data()).unwrap());
+    }
+    let MessageToClient::Response {
+        id: 1,
+        response: Err(err),
+    } = &responses[1]
+    else {
+        panic!("expected an error response, got {:?}", responses[1]);
+    };
+    assert_eq!(err.kind, ErrorKind::PlayerNotConfigured);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_admin_calls_are_routed_to_their_callers() {
+    let port = 9011;
+    let admin_port = 9410;
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--admin-port-range", "9410..9411"]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+
+    // The fake admin interface only responds once both calls have arrived, and in reverse order.
+    let admin_listener = tokio::net::TcpListener::bind(("localhost", admin_port))
+        .await
+        .unwrap();
+    let admin_interface = tokio::spawn(async move {
+        let (stream, _) = admin_listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let mut requests = Vec::new();
+        while requests.len() < 2 {
+            if let Message::Binary(bytes) = ws.next().await.unwrap().unwrap() {
+                requests.push(rmp_serde::from_slice::<FakeHolochainMessage>(&bytes).unwrap());
+            }
+        }
+        for request in requests.into_iter().rev() {
+            let response = FakeHolochainMessage {
+                r#type: "response".to_string(),
+                ..request
+            };
+            ws.send(Message::Binary(rmp_serde::to_vec_named(&response).unwrap()))
+                .await
+                .unwrap();
+        }
+    });
+
+    let call = |message: &[u8]| {
+        trycp_client.request(
+            Request::CallAdminInterface {
+                id: "player_1".to_string(),
+                message: message.to_vec(),
+                timeout_ms: None,
+            },
+            ONE_MIN,
+        )
+    };
+    let (response_1, response_2) = tokio::join!(call(b"first"), call(b"second"));
+    assert_eq!(response_1.unwrap().into_bytes(), b"first");
+    assert_eq!(response_2.unwrap().into_bytes(), b"second");
+
+    admin_interface.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn calls_fail_when_the_conductor_does_not_respond_in_time() {
+    let port = 9012;
+    let admin_port = 9420;
+    let app_port = 9425;
+
+    let (_trycp_server, _) = start_server_with_args(
+        port,
+        &[
+            "--admin-port-range",
+            "9420..9421",
+            "--call-timeout-ms",
+            "500",
+        ],
+    )
+    .await;
+    unresponsive_interface(app_port).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    unresponsive_interface(admin_port).await;
+    let err = trycp_client
+        .request(
+            Request::CallAdminInterface {
+                id: "player_1".to_string(),
+                message: Vec::new(),
+                timeout_ms: Some(200),
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap_err();
+    let err = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<TryCpError>())
+        .unwrap();
+    assert_eq!(err.kind, ErrorKind::Timeout);
+
+    trycp_client
+        .request(
+            Request::ConnectAppInterface {
+                token: Vec::new(),
+                port: app_port,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    // Falls back to the server's default timeout.
+    let err = trycp_client
+        .request(
+            Request::CallAppInterface {
+                port: app_port,
+                message: Vec::new(),
+                timeout_ms: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap_err();
+    let err = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<TryCpError>())
+        .unwrap();
+    assert_eq!(err.kind, ErrorKind::Timeout);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pending_app_calls_fail_and_clients_reconnect_when_the_app_interface_closes() {
+    let port = 9013;
+    let app_port = 9430;
+
+    let (_trycp_server, _) = start_server(port).await;
+
+    // The fake app interface closes the first connection once it has received the authentication
+    // message and a call, then passes on the token that the next connection authenticates with.
+    let app_listener = tokio::net::TcpListener::bind(("localhost", app_port))
+        .await
+        .unwrap();
+    let (token_tx, token_rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let (stream, _) = app_listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let mut messages = 0;
+        while messages < 2 {
+            if let Message::Binary(_) = ws.next().await.unwrap().unwrap() {
+                messages += 1;
+            }
+        }
+        ws.close(None).await.unwrap();
+
+        #[derive(serde::Deserialize)]
+        struct Authenticate {
+            data: serde_bytes::ByteBuf,
+        }
+        #[derive(serde::Deserialize)]
+        struct AuthenticationRequest {
+            token: Vec<u8>,
+        }
+        let (stream, _) = app_listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let Message::Binary(bytes) = ws.next().await.unwrap().unwrap() else {
+            panic!("expected an authentication message");
+        };
+        let authenticate: Authenticate = rmp_serde::from_slice(&bytes).unwrap();
+        let request: AuthenticationRequest = rmp_serde::from_slice(&authenticate.data).unwrap();
+        token_tx.send(request.token).unwrap();
+        // Keep the connection open until the test is done.
+        while ws.next().await.is_some() {}
+    });
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConnectAppInterface {
+                token: Vec::new(),
+                port: app_port,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    let err = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        trycp_client.request(
+            Request::CallAppInterface {
+                port: app_port,
+                message: Vec::new(),
+                timeout_ms: None,
+            },
+            ONE_MIN,
+        ),
+    )
+    .await
+    .expect("pending call should fail when the app interface closes")
+    .unwrap_err();
+    let err = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<TryCpError>())
+        .unwrap();
+    assert_eq!(err.kind, ErrorKind::ConductorConnection);
+
+    // Connecting again replaces the closed connection with one authenticated with the new token.
+    trycp_client
+        .request(
+            Request::ConnectAppInterface {
+                token: vec![1, 2, 3],
+                port: app_port,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    let token = tokio::time::timeout(std::time::Duration::from_secs(10), token_rx)
+        .await
+        .expect("client should reconnect to the app interface")
+        .unwrap();
+    assert_eq!(token, vec![1, 2, 3]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clients_sharing_an_app_interface_get_their_responses_and_all_signals() {
+    let port = 9015;
+    let app_port = 9450;
+
+    let (_trycp_server, _) = start_server(port).await;
+
+    // The fake app interface emits a signal with the content of each call before responding.
+    let app_listener = tokio::net::TcpListener::bind(("localhost", app_port))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        #[derive(serde::Serialize)]
+        struct FakeHolochainSignal {
+            r#type: String,
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+        }
+
+        let (stream, _) = app_listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        while let Some(Ok(message)) = ws.next().await {
+            let Message::Binary(bytes) = message else {
+                continue;
+            };
+            // Skip the authentication message.
+            let Ok(request) = rmp_serde::from_slice::<FakeHolochainMessage>(&bytes) else {
+                continue;
+            };
+            let signal = FakeHolochainSignal {
+                r#type: "signal".to_string(),
+                data: request.data.clone(),
+            };
+            ws.send(Message::Binary(rmp_serde::to_vec_named(&signal).unwrap()))
+                .await
+                .unwrap();
+            let response = FakeHolochainMessage {
+                r#type: "response".to_string(),
+                ..request
+            };
+            ws.send(Message::Binary(rmp_serde::to_vec_named(&response).unwrap()))
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut clients = Vec::new();
+    for _ in 0..2 {
+        let (trycp_client, signals) =
+            trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+                .await
+                .unwrap();
+        trycp_client
+            .request(
+                Request::ConnectAppInterface {
+                    token: Vec::new(),
+                    port: app_port,
+                },
+                ONE_MIN,
+            )
+            .await
+            .unwrap();
+        clients.push((trycp_client, signals));
+    }
+
+    let mut messages = Vec::new();
+    for (trycp_client, _) in &clients {
+        let message = format!("call from {:p}", trycp_client).into_bytes();
+        messages.push(message.clone());
+        let response = trycp_client
+            .request(
+                Request::CallAppInterface {
+                    port: app_port,
+                    message: message.clone(),
+                    timeout_ms: None,
+                },
+                ONE_MIN,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.into_bytes(), message);
+    }
+
+    for (_, signals) in &mut clients {
+        for message in &messages {
+            let signal = tokio::time::timeout(ONE_MIN, signals.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(signal.port, app_port);
+            assert_eq!(&signal.data, message);
+        }
+    }
+
+    // A client disconnecting from the app interface leaves it connected for the others.
+    clients[0]
+        .0
+        .disconnect_app_interface(app_port)
+        .await
+        .unwrap();
+    let response = clients[1]
+        .0
+        .call_app_interface(app_port, b"after disconnect".to_vec(), None)
+        .await
+        .unwrap();
+    assert_eq!(response, b"after disconnect");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn players_of_a_closed_session_are_removed() {
+    let port = 9016;
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nexec sleep 600");
+
+    let (_trycp_server, _) = start_server_with_args(
+        port,
+        &[
+            "--holochain-bin",
+            holochain_bin.to_str().unwrap(),
+            "--on-disconnect",
+            "reset-session",
+        ],
+    )
+    .await;
+
+    let (observer, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    trycp_client
+        .request(
+            Request::Startup {
+                id: "player_1".to_string(),
+                log_level: None,
+                timeout_ms: None,
+                readiness_probe: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    let pid = player_status(&observer, "player_1").await.pid.unwrap();
+
+    drop(trycp_client);
+
+    tokio::time::timeout(ONE_MIN, async {
+        while player_status(&observer, "player_1").await.configured {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("player of closed session should have been removed");
+    assert!(!std::path::Path::new(&format!("/proc/{pid}")).exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn namespaces_keep_players_of_different_runs_apart() {
+    let port = 9017;
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--admin-port-range", "9460..9470"]).await;
+
+    let mut clients = Vec::new();
+    for namespace in ["run_a", "run_b"] {
+        let (trycp_client, _) =
+            trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+                .await
+                .unwrap();
+        let trycp_client = trycp_client.with_namespace(namespace);
+        trycp_client
+            .request(
+                Request::ConfigurePlayer {
+                    id: "player_1".to_string(),
+                    partial_config: "".to_string(),
+                    holochain_version: None,
+                },
+                ONE_MIN,
+            )
+            .await
+            .unwrap();
+        clients.push(trycp_client);
+    }
+    let (run_a, run_b) = (&clients[0], &clients[1]);
+
+    let (all_runs, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    let ids = |players: Vec<PlayerStatus>| {
+        players
+            .into_iter()
+            .map(|player| player.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        ids(list_players(&all_runs).await),
+        ["run_a/player_1", "run_b/player_1"]
+    );
+    assert!(std::env::temp_dir()
+        .join(format!("trycp-{port}/players/.ns/run_a/player_1"))
+        .is_dir());
+    assert_eq!(ids(list_players(run_a).await), ["player_1"]);
+    assert_eq!(player_status(run_a, "player_1").await.id, "player_1");
+
+    // Clients can't reach into a namespace by using its name in a player id.
+    assert!(all_runs
+        .configure_player("run_b/player_2", "", None)
+        .await
+        .is_err());
+    assert!(all_runs.player_status("run_a/player_1").await.is_err());
+
+    // A player without a namespace that is named like one is kept apart from it.
+    all_runs.configure_player("run_a", "", None).await.unwrap();
+
+    run_a.request(Request::Reset, ONE_MIN).await.unwrap();
+
+    assert!(list_players(run_a).await.is_empty());
+    assert!(!player_status(run_a, "player_1").await.configured);
+    assert_eq!(ids(list_players(run_b).await), ["player_1"]);
+    assert!(player_status(run_b, "player_1").await.configured);
+    assert!(player_status(&all_runs, "run_a").await.configured);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn namespaced_clients_call_their_players_admin_interfaces() {
+    let port = 9026;
+    let admin_port = 9520;
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--admin-port-range", "9520..9521"]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    let trycp_client = trycp_client.with_namespace("run_a");
+    trycp_client
+        .configure_player("player_1", "", None)
+        .await
+        .unwrap();
+
+    fake_interface(
+        tokio::net::TcpListener::bind(("localhost", admin_port))
+            .await
+            .unwrap(),
+        |data| data,
+    );
+
+    let response = trycp_client
+        .call_admin_interface("player_1", b"call".to_vec(), None)
+        .await
+        .unwrap();
+    assert_eq!(response, b"call");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn notifications_are_sent_to_the_clients_of_the_players_namespace() {
+    let port = 9027;
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nsleep 1\nexit 3");
+
+    let (_trycp_server, _) = start_server_with_args(
+        port,
+        &[
+            "--holochain-bin",
+            holochain_bin.to_str().unwrap(),
+            "--admin-port-range",
+            "9525..9527",
+        ],
+    )
+    .await;
+
+    let mut clients = Vec::new();
+    for namespace in [Some("run_a"), Some("run_b"), None] {
+        let (trycp_client, _) =
+            trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+                .await
+                .unwrap();
+        let trycp_client = match namespace {
+            Some(namespace) => trycp_client.with_namespace(namespace),
+            None => trycp_client,
+        };
+        // Clients enter their namespace with their first request in it.
+        list_players(&trycp_client).await;
+        let conductor_exits = trycp_client.subscribe_conductor_exits();
+        clients.push((trycp_client, conductor_exits));
+    }
+
+    let run_a = &clients[0].0;
+    run_a.configure_player("player_1", "", None).await.unwrap();
+    run_a.startup("player_1", None, None, None).await.unwrap();
+
+    let exit = tokio::time::timeout(ONE_MIN, clients[0].1.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(exit.id, "player_1");
+    let exit = tokio::time::timeout(ONE_MIN, clients[2].1.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(exit.id, "run_a/player_1");
+    assert!(
+        tokio::time::timeout(std::time::Duration::from_secs(1), clients[1].1.recv())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clients_must_authenticate_when_the_server_requires_it() {
+    let port = 9018;
+    let token_file = std::env::temp_dir().join(format!("trycp-auth-tokens-{port}"));
+    std::fs::write(&token_file, "# CI runners\nsecret\n").unwrap();
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--auth-token-file", token_file.to_str().unwrap()]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    let err = trycp_client
+        .request(Request::ListPlayers, ONE_MIN)
+        .await
+        .unwrap_err();
+    let err = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<TryCpError>())
+        .unwrap();
+    assert_eq!(err.kind, ErrorKind::Unauthenticated);
+
+    trycp_client
+        .request(
+            Request::Authenticate {
+                token: "wrong".to_string(),
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap_err();
+    trycp_client
+        .request(
+            Request::Authenticate {
+                token: "secret".to_string(),
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    assert!(list_players(&trycp_client).await.is_empty());
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect_with_auth_token(
+        format!("ws://localhost:{port}"),
+        "secret",
+    )
+    .await
+    .unwrap();
+    assert!(list_players(&trycp_client).await.is_empty());
+
+    assert!(trycp_client::TrycpClient::connect_with_auth_token(
+        format!("ws://localhost:{port}"),
+        "wrong",
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clients_connect_over_tls_with_a_custom_ca() {
+    let port = 9019;
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_pem = cert.serialize_pem().unwrap();
+    let cert_file = std::env::temp_dir().join(format!("trycp-tls-cert-{port}.pem"));
+    let key_file = std::env::temp_dir().join(format!("trycp-tls-key-{port}.pem"));
+    std::fs::write(&cert_file, &cert_pem).unwrap();
+    std::fs::write(&key_file, cert.serialize_private_key_pem()).unwrap();
+
+    let (_trycp_server, _) = start_server_with_args(
+        port,
+        &[
+            "--tls-cert",
+            cert_file.to_str().unwrap(),
+            "--tls-key",
+            key_file.to_str().unwrap(),
+        ],
+    )
+    .await;
+
+    let connector = trycp_client::tls_connector(cert_pem.as_bytes()).unwrap();
+    let (trycp_client, _) = trycp_client::TrycpClient::connect_with_connector(
+        format!("wss://localhost:{port}"),
+        Some(connector),
+    )
+    .await
+    .unwrap();
+    assert!(list_players(&trycp_client).await.is_empty());
+
+    // The self-signed certificate isn't trusted by default.
+    assert!(
+        trycp_client::TrycpClient::connect(format!("wss://localhost:{port}"))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn holochain_calls_are_encoded_and_zome_calls_signed() {
+    use holochain_conductor_api::{AppRequest, AppResponse};
+    use holochain_zome_types::prelude::*;
+
+    let port = 9021;
+    let admin_port = 9480;
+    let app_port = 9485;
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--admin-port-range", "9480..9481"]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    trycp_client
+        .configure_player("player_1", "", None)
+        .await
+        .unwrap();
+
+    fake_interface(
+        tokio::net::TcpListener::bind(("localhost", admin_port))
+            .await
+            .unwrap(),
+        |data| {
+            let response = match rmp_serde::from_slice(&data).unwrap() {
+                AdminRequest::ListApps { .. } => AdminResponse::AppsListed(Vec::new()),
+                AdminRequest::GrantZomeCallCapability(_) => {
+                    AdminResponse::ZomeCallCapabilityGranted
+                }
+                request => panic!("unexpected admin request {request:?}"),
+            };
+            rmp_serde::to_vec_named(&response).unwrap()
+        },
+    );
+
+    // The fake app interface answers zome calls with a correct signature by the provenance.
+    fake_interface(
+        tokio::net::TcpListener::bind(("localhost", app_port))
+            .await
+            .unwrap(),
+        |data| {
+            let AppRequest::CallZome(call) = rmp_serde::from_slice(&data).unwrap() else {
+                panic!("expected a zome call");
+            };
+            let params: ZomeCallParams = call.bytes.decode().unwrap();
+            let (_, bytes_hash) = params.serialize_and_hash().unwrap();
+            let verifying_key = ed25519_dalek::VerifyingKey::from_bytes(
+                params.provenance.get_raw_32().try_into().unwrap(),
+            )
+            .unwrap();
+            verifying_key
+                .verify_strict(
+                    &bytes_hash,
+                    &ed25519_dalek::Signature::from_bytes(&call.signature.0),
+                )
+                .unwrap();
+            assert!(params.cap_secret.is_some());
+            let output = ExternIO::encode(format!("{} called", params.fn_name)).unwrap();
+            rmp_serde::to_vec_named(&AppResponse::ZomeCalled(Box::new(output))).unwrap()
+        },
+    );
+
+    let response = trycp_client
+        .admin_request(
+            "player_1",
+            AdminRequest::ListApps {
+                status_filter: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert!(matches!(response, AdminResponse::AppsListed(apps) if apps.is_empty()));
+
+    let cell_id = CellId::new(
+        DnaHash::from_raw_32(vec![0; 32]),
+        AgentPubKey::from_raw_32(vec![1; 32]),
+    );
+    let call = trycp_client::CallZomeRequest {
+        cell_id: cell_id.clone(),
+        zome_name: "coordinator".into(),
+        fn_name: "create".into(),
+        payload: ExternIO::encode(()).unwrap(),
+    };
+    trycp_client
+        .connect_app_interface(Vec::new(), app_port)
+        .await
+        .unwrap();
+
+    // Zome calls can't be signed before credentials are authorized for the cell.
+    assert!(trycp_client
+        .call_zome(app_port, call.clone())
+        .await
+        .is_err());
+
+    trycp_client
+        .authorize_signing_credentials("player_1", cell_id, GrantedFunctions::All)
+        .await
+        .unwrap();
+    let output = trycp_client.call_zome(app_port, call).await.unwrap();
+    assert_eq!(output.decode::<String>().unwrap(), "create called");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn scenarios_install_apps_for_players_and_reset_when_dropped() {
+    use holochain_conductor_api::{
+        AppAuthenticationTokenIssued, AppInfo, AppInfoStatus, AppRequest, AppResponse, CellInfo,
+    };
+    use holochain_types::app::{AppBundleSource, AppManifest, AppManifestV1};
+    use holochain_zome_types::prelude::*;
+
+    let port = 9022;
+    let admin_port = 9490;
+    let app_port = 9495;
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nexec sleep 600");
+
+    let (_trycp_server, _) = start_server_with_args(
+        port,
+        &[
+            "--holochain-bin",
+            holochain_bin.to_str().unwrap(),
+            "--admin-port-range",
+            "9490..9491",
+        ],
+    )
+    .await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    let scenario = trycp_client::Scenario::new(trycp_client).with_network_seed("seed");
+    let conductor = scenario.add_conductor().await.unwrap();
+    assert_eq!(conductor.id(), "conductor-1");
+
+    // The fake admin interface installs the app with a single cell for the generated agent.
+    let agent_pub_key = AgentPubKey::from_raw_32(vec![1; 32]);
+    let cell_id = CellId::new(DnaHash::from_raw_32(vec![0; 32]), agent_pub_key.clone());
+    let app_info = AppInfo {
+        installed_app_id: "conductor-1-app".to_string(),
+        cell_info: [(
+            "role".to_string(),
+            vec![CellInfo::new_provisioned(
+                cell_id.clone(),
+                DnaModifiers {
+                    network_seed: "seed".to_string(),
+                    properties: SerializedBytes::default(),
+                    origin_time: Timestamp::now(),
+                    quantum_time: std::time::Duration::from_secs(300),
+                },
+                "dna".to_string(),
+            )],
+        )]
+        .into_iter()
+        .collect(),
+        status: AppInfoStatus::Running,
+        agent_pub_key: agent_pub_key.clone(),
+        manifest: AppManifest::V1(AppManifestV1 {
+            name: "app".to_string(),
+            description: None,
+            roles: Vec::new(),
+            allow_deferred_memproofs: false,
+        }),
+        installed_at: Timestamp::now(),
+    };
+    fake_interface(
+        tokio::net::TcpListener::bind(("localhost", admin_port))
+            .await
+            .unwrap(),
+        move |data| {
+            let response = match rmp_serde::from_slice(&data).unwrap() {
+                AdminRequest::GenerateAgentPubKey => {
+                    AdminResponse::AgentPubKeyGenerated(agent_pub_key.clone())
+                }
+                AdminRequest::InstallApp(payload) => {
+                    let AppBundleSource::Path(path) = &payload.source else {
+                        panic!("expected the hApp to be installed from a path");
+                    };
+                    assert!(path.ends_with("trycp-9022-app.happ"));
+                    assert_eq!(payload.agent_key.as_ref(), Some(&agent_pub_key));
+                    assert_eq!(payload.network_seed.as_deref(), Some("seed"));
+                    AdminResponse::AppInstalled(app_info.clone())
+                }
+                AdminRequest::EnableApp { .. } => AdminResponse::AppEnabled {
+                    app: app_info.clone(),
+                    errors: Vec::new(),
+                },
+                AdminRequest::AttachAppInterface { .. } => {
+                    AdminResponse::AppInterfaceAttached { port: app_port }
+                }
+                AdminRequest::IssueAppAuthenticationToken(_) => {
+                    AdminResponse::AppAuthenticationTokenIssued(AppAuthenticationTokenIssued {
+                        token: vec![1, 2, 3],
+                        expires_at: None,
+                    })
+                }
+                AdminRequest::GrantZomeCallCapability(_) => {
+                    AdminResponse::ZomeCallCapabilityGranted
+                }
+                request => panic!("unexpected admin request {request:?}"),
+            };
+            rmp_serde::to_vec_named(&response).unwrap()
+        },
+    );
+    fake_interface(
+        tokio::net::TcpListener::bind(("localhost", app_port))
+            .await
+            .unwrap(),
+        |data| {
+            let AppRequest::CallZome(call) = rmp_serde::from_slice(&data).unwrap() else {
+                panic!("expected a zome call");
+            };
+            let params: ZomeCallParams = call.bytes.decode().unwrap();
+            let input: String = params.payload.decode().unwrap();
+            let output = ExternIO::encode(format!("{} {input}", params.fn_name)).unwrap();
+            rmp_serde::to_vec_named(&AppResponse::ZomeCalled(Box::new(output))).unwrap()
+        },
+    );
+
+    let happ_path = std::env::temp_dir().join(format!("trycp-{port}-app.happ"));
+    std::fs::write(&happ_path, b"happ").unwrap();
+    let player = conductor
+        .install_app(&trycp_client::AppSource::Path(happ_path))
+        .await
+        .unwrap();
+    assert_eq!(player.app_port, app_port);
+    assert_eq!(player.cell_id("role").unwrap(), &cell_id);
+    assert!(player.cell_id("other").is_err());
+
+    let output: String = player
+        .call_zome("role", "coordinator", "create", "entry".to_string())
+        .await
+        .unwrap();
+    assert_eq!(output, "create entry");
+
+    // Dropping the scenario resets the server before the drop returns.
+    drop(player);
+    drop(conductor);
+    drop(scenario);
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    assert!(list_players(&trycp_client).await.is_empty());
+}
+
+#[tokio::test]
+async fn scenarios_are_cleaned_up_on_current_thread_runtimes() {
+    let port = 9028;
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nexec sleep 600");
+
+    let (_trycp_server, _) = start_server_with_args(
+        port,
+        &[
+            "--holochain-bin",
+            holochain_bin.to_str().unwrap(),
+            "--admin-port-range",
+            "9530..9532",
+        ],
+    )
+    .await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    // The reset can't block a current-thread runtime, so a dropped scenario leaves its players.
+    let scenario = trycp_client::Scenario::new(trycp_client);
+    let namespace = scenario.namespace().to_string();
+    scenario.add_conductor().await.unwrap();
+    drop(scenario);
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    let players = list_players(&trycp_client).await;
+    assert_eq!(players.len(), 1);
+    assert_eq!(players[0].id, format!("{namespace}/conductor-1"));
+
+    // Cleaning up a scenario waits for the reset, which removes the players left before in the
+    // same namespace.
+    let scenario = trycp_client::Scenario::new(trycp_client.with_namespace(namespace));
+    scenario.cleanup().await.unwrap();
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    assert!(list_players(&trycp_client).await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dht_sync_waits_until_players_integrated_the_same_ops() {
+    use holochain_types::prelude::*;
+
+    let port = 9023;
+    let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nexec sleep 600");
+
+    let (_trycp_server, _) = start_server_with_args(
+        port,
+        &[
+            "--holochain-bin",
+            holochain_bin.to_str().unwrap(),
+            "--admin-port-range",
+            "9500..9502",
+        ],
+    )
+    .await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    let scenario = trycp_client::Scenario::new(trycp_client);
+    let op = |seconds: i64| {
+        DhtOp::from(ChainOp::RegisterAgentActivity(
+            Signature([0; 64]),
+            Action::Dna(Dna {
+                author: AgentPubKey::from_raw_32(vec![0; 32]),
+                timestamp: Timestamp::from_micros(seconds * 1_000_000),
+                hash: DnaHash::from_raw_32(vec![0; 32]),
+            }),
+        ))
+    };
+    let integrated_1 = std::sync::Arc::new(std::sync::Mutex::new(vec![op(1), op(2)]));
+    let integrated_2 = std::sync::Arc::new(std::sync::Mutex::new(vec![op(1)]));
+
+    let mut players = Vec::new();
+    for (app_port, integrated) in [(9505, &integrated_1), (9506, &integrated_2)] {
+        let conductor = scenario.add_conductor().await.unwrap();
+        let admin_port = scenario
+            .client()
+            .player_status(conductor.id())
+            .await
+            .unwrap()
+            .admin_port
+            .unwrap();
+        fake_app_conductor(admin_port, app_port, integrated.clone()).await;
+        let happ_path = std::env::temp_dir().join(format!("trycp-{port}-app.happ"));
+        std::fs::write(&happ_path, b"happ").unwrap();
+        players.push(
+            conductor
+                .install_app(&trycp_client::AppSource::Path(happ_path))
+                .await
+                .unwrap(),
+        );
+    }
+    let players: Vec<_> = players.iter().collect();
+
+    let err = trycp_client::dht_sync(
+        &players,
+        "role",
+        std::time::Duration::from_millis(100),
+        std::time::Duration::from_millis(300),
+    )
+    .await
+    .unwrap_err();
+    let timeout = err
+        .get_ref()
+        .unwrap()
+        .downcast_ref::<trycp_client::DhtSyncTimeout>()
+        .unwrap();
+    assert_eq!(timeout.missing.len(), 1);
+    assert_eq!(timeout.missing[0].player_id, players[1].conductor.id());
+    assert_eq!(
+        timeout.missing[0].op_hashes.iter().collect::<Vec<_>>(),
+        vec![&DhtOpHash::with_data_sync(&op(2))]
+    );
+
+    // The second player integrates the missing op while the first sync is waiting.
+    let sync = trycp_client::dht_sync(
+        &players,
+        "role",
+        std::time::Duration::from_millis(100),
+        ONE_MIN,
+    );
+    let integrate = async {
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        integrated_2.lock().unwrap().push(op(2));
+    };
+    let (result, _) = tokio::join!(sync, integrate);
+    result.unwrap();
+
+    scenario.cleanup().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn agent_infos_are_exchanged_between_conductors_on_different_servers() {
+    use kitsune_p2p_types::{
+        agent_info::AgentInfoSigned,
+        bin_types::{KitsuneAgent, KitsuneSpace},
+        dht::arq::ArqSize,
+    };
+    use std::sync::{Arc, Mutex};
+
+    let mut scenarios = Vec::new();
+    let mut conductors = Vec::new();
+    let mut agent_infos = Vec::new();
+    let mut added_agent_infos = Vec::new();
+    for (port, admin_port_range, agent) in [(9024, "9510..9511", 1), (9025, "9515..9516", 2)] {
+        let holochain_bin = fake_holochain(port, "echo \"Conductor ready.\"\nexec sleep 600");
+        let (trycp_server, _) = start_server_with_args(
+            port,
+            &[
+                "--holochain-bin",
+                holochain_bin.to_str().unwrap(),
+                "--admin-port-range",
+                admin_port_range,
+            ],
+        )
+        .await;
+        let (trycp_client, _) =
+            trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+                .await
+                .unwrap();
+        let scenario = trycp_client::Scenario::new(trycp_client);
+        let conductor = scenario.add_conductor().await.unwrap();
+        let admin_port = scenario
+            .client()
+            .player_status(conductor.id())
+            .await
+            .unwrap()
+            .admin_port
+            .unwrap();
+
+        let agent_info = AgentInfoSigned::sign(
+            Arc::new(KitsuneSpace(vec![0; 36])),
+            Arc::new(KitsuneAgent(vec![agent; 36])),
+            ArqSize::empty(),
+            Vec::new(),
+            0,
+            1,
+            |_| async move { Ok(Arc::new(vec![0; 64].into())) },
+        )
+        .await
+        .unwrap();
+        let added = Arc::new(Mutex::new(Vec::new()));
+        fake_interface(
+            tokio::net::TcpListener::bind(("localhost", admin_port))
+                .await
+                .unwrap(),
+            {
+                let agent_info = agent_info.clone();
+                let added = added.clone();
+                move |data| {
+                    let response = match rmp_serde::from_slice(&data).unwrap() {
+                        AdminRequest::AgentInfo { cell_id: None } => {
+                            AdminResponse::AgentInfo(vec![agent_info.clone()])
+                        }
+                        AdminRequest::AddAgentInfo { agent_infos } => {
+                            added.lock().unwrap().extend(agent_infos);
+                            AdminResponse::AgentInfoAdded
+                        }
+                        request => panic!("unexpected admin request {request:?}"),
+                    };
+                    rmp_serde::to_vec_named(&response).unwrap()
+                }
+            },
+        );
+
+        scenarios.push((trycp_server, scenario));
+        conductors.push(conductor);
+        agent_infos.push(agent_info);
+        added_agent_infos.push(added);
+    }
+
+    trycp_client::add_all_agents_to_all_conductors(&conductors.iter().collect::<Vec<_>>())
+        .await
+        .unwrap();
+    assert_eq!(
+        *added_agent_infos[0].lock().unwrap(),
+        vec![agent_infos[1].clone()]
+    );
+    assert_eq!(
+        *added_agent_infos[1].lock().unwrap(),
+        vec![agent_infos[0].clone()]
+    );
+
+    for (_, scenario) in scenarios {
+        scenario.cleanup().await.unwrap();
+    }
+}
+
+/// Stand in for the admin and app interfaces of a conductor that installs an app with a single
+/// cell of role "role" and reports the given ops as integrated.
+async fn fake_app_conductor(
+    admin_port: u16,
+    app_port: u16,
+    integrated: std::sync::Arc<std::sync::Mutex<Vec<holochain_types::dht_op::DhtOp>>>,
+) {
+    use holochain_conductor_api::{
+        AppAuthenticationTokenIssued, AppInfo, AppInfoStatus, CellInfo, FullIntegrationStateDump,
+        FullStateDump, P2pAgentsDump,
+    };
+    use holochain_types::app::{AppManifest, AppManifestV1};
+    use holochain_zome_types::prelude::*;
+
+    let agent_pub_key = AgentPubKey::from_raw_32(vec![admin_port as u8; 32]);
+    let cell_id = CellId::new(DnaHash::from_raw_32(vec![0; 32]), agent_pub_key.clone());
+    let app_info = AppInfo {
+        installed_app_id: "app".to_string(),
+        cell_info: [(
+            "role".to_string(),
+            vec![CellInfo::new_provisioned(
+                cell_id,
+                DnaModifiers {
+                    network_seed: String::new(),
+                    properties: SerializedBytes::default(),
+                    origin_time: Timestamp::now(),
+                    quantum_time: std::time::Duration::from_secs(300),
+                },
+                "dna".to_string(),
+            )],
+        )]
+        .into_iter()
+        .collect(),
+        status: AppInfoStatus::Running,
+        agent_pub_key: agent_pub_key.clone(),
+        manifest: AppManifest::V1(AppManifestV1 {
+            name: "app".to_string(),
+            description: None,
+            roles: Vec::new(),
+            allow_deferred_memproofs: false,
+        }),
+        installed_at: Timestamp::now(),
+    };
+    fake_interface(
+        tokio::net::TcpListener::bind(("localhost", admin_port))
+            .await
+            .unwrap(),
+        move |data| {
+            let response = match rmp_serde::from_slice(&data).unwrap() {
+                AdminRequest::GenerateAgentPubKey => {
+                    AdminResponse::AgentPubKeyGenerated(agent_pub_key.clone())
+                }
+                AdminRequest::InstallApp(_) => AdminResponse::AppInstalled(app_info.clone()),
+                AdminRequest::EnableApp { .. } => AdminResponse::AppEnabled {
+                    app: app_info.clone(),
+                    errors: Vec::new(),
+                },
+                AdminRequest::AttachAppInterface { .. } => {
+                    AdminResponse::AppInterfaceAttached { port: app_port }
+                }
+                AdminRequest::IssueAppAuthenticationToken(_) => {
+                    AdminResponse::AppAuthenticationTokenIssued(AppAuthenticationTokenIssued {
+                        token: Vec::new(),
+                        expires_at: None,
+                    })
+                }
+                AdminRequest::GrantZomeCallCapability(_) => {
+                    AdminResponse::ZomeCallCapabilityGranted
+                }
+                AdminRequest::DumpFullState { .. } => {
+                    AdminResponse::FullStateDumped(FullStateDump {
+                        peer_dump: P2pAgentsDump {
+                            this_agent_info: None,
+                            this_dna: None,
+                            this_agent: None,
+                            peers: Vec::new(),
+                        },
+                        source_chain_dump: holochain_state_types::SourceChainDump {
+                            records: Vec::new(),
+                            published_ops_count: 0,
+                        },
+                        integration_dump: FullIntegrationStateDump {
+                            validation_limbo: Vec::new(),
+                            integration_limbo: Vec::new(),
+                            integrated: integrated.lock().unwrap().clone(),
+                            dht_ops_cursor: 0,
+                        },
+                    })
+                }
+                request => panic!("unexpected admin request {request:?}"),
+            };
+            rmp_serde::to_vec_named(&response).unwrap()
+        },
+    );
+    fake_interface(
+        tokio::net::TcpListener::bind(("localhost", app_port))
+            .await
+            .unwrap(),
+        |_| panic!("unexpected app request"),
+    );
+}
+
+async fn list_players(trycp_client: &trycp_client::TrycpClient) -> Vec<PlayerStatus> {
+    trycp_client.list_players().await.unwrap()
+}
+
+/// Accept websocket connections on the given port and never respond to anything sent over them.
+async fn unresponsive_interface(port: u16) {
+    let listener = tokio::net::TcpListener::bind(("localhost", port))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                while let Some(Ok(_)) = ws.next().await {}
+            });
+        }
+    });
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_interface_signals_are_forwarded_to_clients() {
+    let port = 9014;
+    let admin_port = 9440;
+
+    let (_trycp_server, _) =
+        start_server_with_args(port, &["--admin-port-range", "9440..9441"]).await;
+
+    let (trycp_client, _) = trycp_client::TrycpClient::connect(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+    let mut admin_signals = trycp_client.subscribe_admin_signals();
+    let (mut legacy_client, _) = tokio_tungstenite::connect_async(format!("ws://localhost:{port}"))
+        .await
+        .unwrap();
+
+    trycp_client
+        .request(
+            Request::ConfigurePlayer {
+                id: "player_1".to_string(),
+                partial_config: "".to_string(),
+                holochain_version: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+
+    // The fake admin interface emits a signal before responding to the call.
+    let admin_listener = tokio::net::TcpListener::bind(("localhost", admin_port))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        #[derive(serde::Serialize)]
+        struct FakeHolochainSignal {
+            r#type: String,
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+        }
+
+        let (stream, _) = admin_listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        loop {
+            if let Message::Binary(bytes) = ws.next().await.unwrap().unwrap() {
+                let request = rmp_serde::from_slice::<FakeHolochainMessage>(&bytes).unwrap();
+                let signal = FakeHolochainSignal {
+                    r#type: "signal".to_string(),
+                    data: b"signal".to_vec(),
+                };
+                ws.send(Message::Binary(rmp_serde::to_vec_named(&signal).unwrap()))
+                    .await
+                    .unwrap();
+                let response = FakeHolochainMessage {
+                    r#type: "response".to_string(),
+                    ..request
+                };
+                ws.send(Message::Binary(rmp_serde::to_vec_named(&response).unwrap()))
+                    .await
+                    .unwrap();
+            }
+        }
+    });
+
+    let response = trycp_client
+        .request(
+            Request::CallAdminInterface {
+                id: "player_1".to_string(),
+                message: b"call".to_vec(),
+                timeout_ms: None,
+            },
+            ONE_MIN,
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.into_bytes(), b"call");
+
+    let signal = tokio::time::timeout(ONE_MIN, admin_signals.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(signal.id, "player_1");
+    assert_eq!(signal.data, b"signal");
+
+    // Clients that didn't ask for admin signals in a hello don't receive them.
+    assert!(tokio::time::timeout(
+        std::time::Duration::from_secs(1),
+        futures::StreamExt::next(&mut legacy_client)
+    )
+    .await
+    .is_err());
+}
+
+/// The envelope of messages on Holochain's websocket interfaces.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FakeHolochainMessage {
+    r#type: String,
+    id: u64,
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+}
+
+/// Answer every request sent to the given listener's websocket connections with the response
+/// computed from the request's data.
+fn fake_interface(
+    listener: tokio::net::TcpListener,
+    respond: impl Fn(Vec<u8>) -> Vec<u8> + Clone + Send + 'static,
+) {
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let respond = respond.clone();
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                while let Some(Ok(message)) = ws.next().await {
+                    let Message::Binary(bytes) = message else {
+                        continue;
+                    };
+                    // Skip app interface authentication messages.
+                    let Ok(request) = rmp_serde::from_slice::<FakeHolochainMessage>(&bytes) else {
+                        continue;
+                    };
+                    let response = FakeHolochainMessage {
+                        r#type: "response".to_string(),
+                        id: request.id,
+                        data: respond(request.data),
+                    };
+                    ws.send(Message::Binary(rmp_serde::to_vec_named(&response).unwrap()))
+                        .await
+                        .unwrap();
+                }
+            });
+        }
+    });
+}
+
+async fn player_status(trycp_client: &trycp_client::TrycpClient, id: &str) -> PlayerStatus {
+    trycp_client.player_status(id).await.unwrap()
+}
+
+/// Write a shell script that stands in for the holochain binary.
+fn fake_holochain(port: u16, script: &str) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::temp_dir().join(format!("trycp-fake-holochain-{port}"));
+    std::fs::write(
+        &path,
+        format!(
+            "#!/bin/sh\nif [ \"$1\" = \"--version\" ]; then echo \"holochain 0.0.0-fake\"; exit 0; fi\n{script}\n"
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+async fn start_server(port: u16) -> (tokio::process::Child, Receiver<String>) {
+    start_server_with_args(port, &[]).await
+}
+
+async fn start_server_with_args(
+    port: u16,
+    args: &[&str],
+) -> (tokio::process::Child, Receiver<String>) {
     let mut server = tokio::process::Command::new("cargo")
         .arg("run")
         .arg("-p")
@@ -135,6 +2313,9 @@ async fn start_server(port: u16) -> (tokio::process::Child, Receiver<String>) {
         .arg("--")
         .arg("-p")
         .arg(port.to_string())
+        .arg("--data-dir")
+        .arg(std::env::temp_dir().join(format!("trycp-{port}")))
+        .args(args)
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
         .kill_on_drop(true)
@@ -161,7 +2342,7 @@ fn spawn_output(
         let mut reader = BufReader::new(stdout).lines();
         while let Ok(Some(line)) = reader.next_line().await {
             println!("trycp_server stdout: {}", &line);
-            if let Some(_) = line.strip_prefix("Listening on ") {
+            if line.strip_prefix("Listening on ").is_some() {
                 if let Some(tx) = tx.take() {
                     let _ = tx.send(());
                 }
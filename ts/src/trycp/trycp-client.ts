@@ -133,6 +133,12 @@ export class TryCpClient {
           throw new Error("Unknown response type");
         }
         delete tryCpClient.requestPromises[responseWrapper.id];
+      } else {
+        logger.debug(
+          `ignoring TryCP server message of type ${
+            (responseWrapper as { type: string }).type
+          }`
+        );
       }
     });
     tryCpClient.ws.on("error", (err) => {
@@ -77,7 +77,9 @@ function assertIsResponseWrapper(
       // responses contain "id" and "response"
       (("id" in response && "response" in response) ||
         //and signals contain "port" and "data"
-        ("port" in response && "data" in response))
+        ("port" in response && "data" in response) ||
+        // other messages, like notifications, are ignored by the client
+        (response.type !== "response" && response.type !== "signal"))
   );
 }
 